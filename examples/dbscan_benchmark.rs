@@ -0,0 +1,79 @@
+//! Compares DBSCAN neighbour lookup with and without the spatial grid on a
+//! synthetic summer hour of strikes over Scandinavia.
+//!
+//! Run with `cargo run --release --example dbscan_benchmark [strikes]`.
+
+use std::time::Instant;
+
+use lightning_warning::{
+    dbscan::{cluster_lightning, DbscanParams, NeighborIndex},
+    ualf::UalfData,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const STORMS: usize = 40;
+const NOISE_FRACTION: f64 = 0.05;
+
+/// Strikes scattered around storm cells within 55-71°N, 4-31°E, with some noise.
+fn synthetic_strikes(count: usize, rng: &mut StdRng) -> Vec<UalfData> {
+    let storms: Vec<(f64, f64)> = (0..STORMS)
+        .map(|_| (rng.gen_range(55.0..71.0), rng.gen_range(4.0..31.0)))
+        .collect();
+
+    (0..count)
+        .map(|i| {
+            let epoch_ns = 1_719_835_200_000_000_000 + rng.gen_range(0..3_600_000_000_000);
+            let peak_current = rng.gen_range(-60..60);
+            if rng.gen_bool(NOISE_FRACTION) {
+                return UalfData::synthetic(
+                    epoch_ns,
+                    rng.gen_range(55.0..71.0),
+                    rng.gen_range(4.0..31.0),
+                    peak_current,
+                );
+            }
+            let (latitude, longitude) = storms[i % STORMS];
+            UalfData::synthetic(
+                epoch_ns,
+                latitude + rng.gen_range(-0.3..0.3),
+                longitude + rng.gen_range(-0.6..0.6),
+                peak_current,
+            )
+        })
+        .collect()
+}
+
+fn run(data: &[UalfData], index: NeighborIndex) -> (u128, Vec<usize>) {
+    let params = DbscanParams {
+        index,
+        ..DbscanParams::default()
+    };
+    let now = Instant::now();
+    let result = cluster_lightning(data, &params);
+    let elapsed = now.elapsed().as_millis();
+
+    let mut sizes: Vec<usize> = result.clusters.iter().map(|c| c.points.len()).collect();
+    sizes.sort_unstable();
+    (elapsed, sizes)
+}
+
+fn main() {
+    let count: usize = std::env::args()
+        .nth(1)
+        .and_then(|arg| arg.parse().ok())
+        .unwrap_or(50_000);
+    let data = synthetic_strikes(count, &mut StdRng::seed_from_u64(42));
+    println!("{} synthetic strikes", data.len());
+
+    let (grid_ms, grid_sizes) = run(&data, NeighborIndex::Grid);
+    println!("grid:        {:>8}ms, {} clusters", grid_ms, grid_sizes.len());
+
+    let (brute_ms, brute_sizes) = run(&data, NeighborIndex::BruteForce);
+    println!("brute force: {:>8}ms, {} clusters", brute_ms, brute_sizes.len());
+
+    assert_eq!(grid_sizes, brute_sizes, "grid and brute force clusters differ");
+    println!(
+        "speedup:     {:>8.1}x",
+        brute_ms as f64 / grid_ms.max(1) as f64
+    );
+}
@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::db::{AlertTransition, LocationAlert, Observation, UserLocation};
+
+/// The 30-minute rule: stay inside until 30 minutes after the last strike.
+pub const DEFAULT_ALL_CLEAR: Duration = Duration::from_secs(30 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertState {
+    Clear,    // No lightning within radius since the location was registered
+    Warning,  // Lightning within radius, and not long enough ago to be safe
+    AllClear, // Lightning within radius, but long enough ago to go back outside
+}
+
+/// What changed in one update of the alert states.
+#[derive(Debug)]
+pub struct AlertUpdate {
+    pub alerts: Vec<LocationAlert>, // Alerts that changed and need persisting
+    pub transitions: Vec<AlertTransition>,
+}
+
+/// Tracks the alert state of every user location.
+///
+/// A strike within radius puts a location in `Warning`, and it returns to
+/// `AllClear` once its `all_clear_minutes`, or `DEFAULT_ALL_CLEAR`, have
+/// passed without another one.
+pub struct AlertTracker {
+    alerts: HashMap<i64, LocationAlert>,
+}
+
+impl AlertTracker {
+    /// Resumes from the alerts last persisted.
+    pub fn new(alerts: Vec<LocationAlert>) -> AlertTracker {
+        AlertTracker {
+            alerts: alerts
+                .into_iter()
+                .map(|alert| (alert.location_id, alert))
+                .collect(),
+        }
+    }
+
+    /// Applies the latest strikes within radius at time `now_ns`.
+    pub fn update(
+        &mut self,
+        now_ns: i64,
+        locations: &[UserLocation],
+        observations: &[Observation],
+    ) -> AlertUpdate {
+        let mut newest_strikes: HashMap<i64, i64> = HashMap::new();
+        for observation in observations {
+            let newest = newest_strikes
+                .entry(observation.location_id)
+                .or_insert(observation.epoch_ns);
+            *newest = (*newest).max(observation.epoch_ns);
+        }
+
+        let mut update = AlertUpdate {
+            alerts: vec![],
+            transitions: vec![],
+        };
+        for location in locations {
+            let alert = self
+                .alerts
+                .entry(location.id)
+                .or_insert_with(|| LocationAlert {
+                    location_id: location.id,
+                    state: AlertState::Clear,
+                    last_strike_epoch_ns: None,
+                });
+            let previous_state = alert.state;
+            let all_clear_ns = location
+                .all_clear_minutes
+                .map_or(DEFAULT_ALL_CLEAR, |minutes| {
+                    Duration::from_secs(minutes.max(0) as u64 * 60)
+                })
+                .as_nanos() as i64;
+            let mut changed = false;
+            let mut transition_epoch_ns = now_ns;
+
+            let newest_strike = newest_strikes.get(&location.id).copied();
+            if let Some(newest_strike) = newest_strike {
+                let last_strike = alert
+                    .last_strike_epoch_ns
+                    .map_or(newest_strike, |last| last.max(newest_strike));
+                changed = alert.last_strike_epoch_ns != Some(last_strike);
+                alert.last_strike_epoch_ns = Some(last_strike);
+            }
+
+            // Strikes delivered late may already be older than the all-clear time
+            let last_strike = alert.last_strike_epoch_ns.unwrap_or(i64::MIN);
+            let recent = now_ns.saturating_sub(last_strike) < all_clear_ns;
+            if recent && newest_strike.is_some() && alert.state != AlertState::Warning {
+                alert.state = AlertState::Warning;
+                transition_epoch_ns = last_strike;
+            } else if !recent && alert.state == AlertState::Warning {
+                alert.state = AlertState::AllClear;
+                transition_epoch_ns = last_strike.saturating_add(all_clear_ns);
+            }
+
+            if alert.state != previous_state {
+                info!(
+                    "[ALERT] location {} went from {:?} to {:?}",
+                    location.id, previous_state, alert.state
+                );
+                update.transitions.push(AlertTransition {
+                    location_id: location.id,
+                    from_state: previous_state,
+                    to_state: alert.state,
+                    epoch_ns: transition_epoch_ns,
+                });
+                changed = true;
+            }
+            if changed {
+                update.alerts.push(alert.clone());
+            }
+        }
+
+        update
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE_NS: i64 = 60 * 1_000_000_000;
+
+    fn location(id: i64, all_clear_minutes: Option<i16>) -> UserLocation {
+        UserLocation {
+            id,
+            uuid: format!("location-{}", id),
+            latitude: 60.0,
+            longitude: 10.0,
+            radius_km: 10,
+            all_clear_minutes,
+        }
+    }
+
+    fn strike(location_id: i64, epoch_ns: i64) -> Observation {
+        Observation {
+            epoch_ns,
+            latitude: 60.0,
+            longitude: 10.0,
+            peak_current: -12,
+            cloud_indicator: false,
+            distance_m: 1000,
+            location_id,
+        }
+    }
+
+    fn states(update: &AlertUpdate) -> Vec<(AlertState, AlertState, i64)> {
+        update
+            .transitions
+            .iter()
+            .map(|t| (t.from_state, t.to_state, t.epoch_ns))
+            .collect()
+    }
+
+    #[test]
+    fn goes_from_clear_to_warning_to_all_clear() {
+        let locations = [location(1, None)];
+        let mut tracker = AlertTracker::new(vec![]);
+
+        let quiet = tracker.update(0, &locations, &[]);
+        assert!(quiet.transitions.is_empty());
+        assert_eq!(quiet.alerts.len(), 0);
+
+        let warning = tracker.update(MINUTE_NS, &locations, &[strike(1, MINUTE_NS / 2)]);
+        assert_eq!(
+            states(&warning),
+            vec![(AlertState::Clear, AlertState::Warning, MINUTE_NS / 2)]
+        );
+        assert_eq!(warning.alerts[0].last_strike_epoch_ns, Some(MINUTE_NS / 2));
+
+        // A strike later on pushes the all-clear back without a transition
+        let later = tracker.update(20 * MINUTE_NS, &locations, &[strike(1, 20 * MINUTE_NS)]);
+        assert!(later.transitions.is_empty());
+        assert_eq!(later.alerts[0].state, AlertState::Warning);
+
+        let still_warning = tracker.update(49 * MINUTE_NS, &locations, &[]);
+        assert!(still_warning.transitions.is_empty());
+        assert!(still_warning.alerts.is_empty());
+
+        let all_clear = tracker.update(51 * MINUTE_NS, &locations, &[]);
+        assert_eq!(
+            states(&all_clear),
+            vec![(AlertState::Warning, AlertState::AllClear, 50 * MINUTE_NS)]
+        );
+
+        let again = tracker.update(60 * MINUTE_NS, &locations, &[strike(1, 59 * MINUTE_NS)]);
+        assert_eq!(
+            states(&again),
+            vec![(AlertState::AllClear, AlertState::Warning, 59 * MINUTE_NS)]
+        );
+    }
+
+    #[test]
+    fn a_late_strike_already_past_the_all_clear_time_does_not_warn() {
+        let locations = [location(1, None)];
+        let mut tracker = AlertTracker::new(vec![]);
+
+        let update = tracker.update(45 * MINUTE_NS, &locations, &[strike(1, 10 * MINUTE_NS)]);
+        assert!(update.transitions.is_empty());
+        assert_eq!(update.alerts[0].state, AlertState::Clear);
+        assert_eq!(update.alerts[0].last_strike_epoch_ns, Some(10 * MINUTE_NS));
+    }
+
+    #[test]
+    fn a_late_strike_older_than_the_last_one_keeps_the_newest() {
+        let locations = [location(1, None)];
+        let mut tracker = AlertTracker::new(vec![]);
+        tracker.update(10 * MINUTE_NS, &locations, &[strike(1, 10 * MINUTE_NS)]);
+
+        let late = tracker.update(12 * MINUTE_NS, &locations, &[strike(1, 5 * MINUTE_NS)]);
+        assert!(late.transitions.is_empty());
+        assert!(late.alerts.is_empty());
+
+        let all_clear = tracker.update(41 * MINUTE_NS, &locations, &[]);
+        assert_eq!(
+            states(&all_clear),
+            vec![(AlertState::Warning, AlertState::AllClear, 40 * MINUTE_NS)]
+        );
+    }
+
+    #[test]
+    fn each_location_has_its_own_all_clear_time() {
+        let locations = [location(1, Some(10)), location(2, None)];
+        let mut tracker = AlertTracker::new(vec![]);
+        tracker.update(0, &locations, &[strike(1, 0), strike(2, 0)]);
+
+        let update = tracker.update(15 * MINUTE_NS, &locations, &[]);
+        assert_eq!(update.transitions.len(), 1);
+        assert_eq!(update.transitions[0].location_id, 1);
+        assert_eq!(update.transitions[0].epoch_ns, 10 * MINUTE_NS);
+
+        let update = tracker.update(30 * MINUTE_NS, &locations, &[]);
+        assert_eq!(update.transitions.len(), 1);
+        assert_eq!(update.transitions[0].location_id, 2);
+    }
+
+    #[test]
+    fn resumes_from_persisted_alerts() {
+        let mut tracker = AlertTracker::new(vec![LocationAlert {
+            location_id: 1,
+            state: AlertState::Warning,
+            last_strike_epoch_ns: Some(0),
+        }]);
+        let update = tracker.update(31 * MINUTE_NS, &[location(1, None)], &[]);
+        assert_eq!(
+            states(&update),
+            vec![(AlertState::Warning, AlertState::AllClear, 30 * MINUTE_NS)]
+        );
+    }
+}
@@ -0,0 +1,152 @@
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CircuitState {
+    Closed,   // Requests flow normally
+    Open,     // Requests are refused until the cooldown has passed
+    HalfOpen, // A single trial request is allowed through
+}
+
+/// Stops hammering a failing upstream. After `failure_threshold` consecutive
+/// failures the circuit opens, and after `cooldown` a trial request decides
+/// whether it closes again.
+pub struct CircuitBreaker {
+    name: String,
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    pub failure_threshold: u32,
+    pub cooldown: Duration,
+}
+
+impl CircuitBreaker {
+    pub fn new(name: &str, failure_threshold: u32, cooldown: Duration) -> CircuitBreaker {
+        CircuitBreaker {
+            name: name.to_string(),
+            state: CircuitState::Closed,
+            consecutive_failures: 0,
+            opened_at: None,
+            failure_threshold,
+            cooldown,
+        }
+    }
+
+    pub fn state(&self) -> CircuitState {
+        self.state
+    }
+
+    pub fn opened_at(&self) -> Option<Instant> {
+        self.opened_at
+    }
+
+    pub fn allow_request(&mut self) -> bool {
+        match self.state {
+            CircuitState::Closed => true,
+            CircuitState::HalfOpen => false,
+            CircuitState::Open => {
+                let cooled_down = self
+                    .opened_at
+                    .is_none_or(|opened_at| opened_at.elapsed() >= self.cooldown);
+                if cooled_down {
+                    info!("[{}] circuit half-open, trying a request", self.name);
+                    self.state = CircuitState::HalfOpen;
+                }
+                cooled_down
+            }
+        }
+    }
+
+    pub fn record_success(&mut self) {
+        if self.state != CircuitState::Closed {
+            info!("[{}] circuit closed, upstream recovered", self.name);
+        }
+        self.state = CircuitState::Closed;
+        self.consecutive_failures = 0;
+        self.opened_at = None;
+    }
+
+    pub fn record_failure(&mut self) {
+        self.consecutive_failures += 1;
+        let should_open = self.state == CircuitState::HalfOpen
+            || self.consecutive_failures >= self.failure_threshold;
+        if should_open {
+            warn!(
+                "[{}] circuit open after {} consecutive failures, pausing requests for {:?}",
+                self.name, self.consecutive_failures, self.cooldown
+            );
+            self.state = CircuitState::Open;
+            self.opened_at = Some(Instant::now());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_after_the_failure_threshold() {
+        let mut breaker = CircuitBreaker::new("TEST", 3, Duration::from_secs(60));
+        for _ in 0..2 {
+            assert!(breaker.allow_request());
+            breaker.record_failure();
+            assert_eq!(breaker.state(), CircuitState::Closed);
+        }
+
+        // A success in between starts the count over
+        breaker.record_success();
+        breaker.record_failure();
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(breaker.opened_at().is_some());
+        // Still cooling down
+        assert!(!breaker.allow_request());
+        assert_eq!(breaker.state(), CircuitState::Open);
+    }
+
+    #[test]
+    fn lets_one_trial_through_after_the_cooldown() {
+        let mut breaker = CircuitBreaker::new("TEST", 1, Duration::ZERO);
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        assert!(breaker.allow_request());
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        // Only the one trial until it has an outcome
+        assert!(!breaker.allow_request());
+    }
+
+    #[test]
+    fn a_failed_trial_reopens_the_circuit() {
+        let mut breaker = CircuitBreaker::new("TEST", 5, Duration::ZERO);
+        for _ in 0..5 {
+            breaker.record_failure();
+        }
+        assert!(breaker.allow_request());
+
+        breaker.cooldown = Duration::from_secs(60);
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(!breaker.allow_request());
+    }
+
+    #[test]
+    fn a_successful_trial_closes_the_circuit() {
+        let mut breaker = CircuitBreaker::new("TEST", 2, Duration::ZERO);
+        breaker.record_failure();
+        breaker.record_failure();
+        assert!(breaker.allow_request());
+
+        breaker.record_success();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert_eq!(breaker.opened_at(), None);
+        // The threshold applies afresh
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+
+use delaunator::{triangulate, Point, EMPTY};
+
+use crate::{
+    convex_hull::compute_convex_hull_of_points, projection::AzimuthalEquidistant, ualf::UalfData,
+};
+
+/// Computes the alpha shape of the points: the union of the Delaunay
+/// triangles whose circumcircle is no wider than `alpha_km` in radius.
+///
+/// A long squall line or an L-shaped storm gets an outline that follows it
+/// rather than spanning the empty area beside it, at the cost of dropping
+/// strikes too far from any other to be part of a triangle. Separate parts
+/// come back as separate polygons and holes are filled. Falls back to the
+/// convex hull when no triangle is small enough.
+pub fn compute_concave_hull(points: Vec<UalfData>, alpha_km: f64) -> Vec<Vec<(f64, f64)>> {
+    let points: Vec<(f64, f64)> = points.iter().map(|p| (p.latitude, p.longitude)).collect();
+
+    compute_concave_hull_of_points(points, alpha_km)
+}
+
+pub fn compute_concave_hull_of_points(
+    points: Vec<(f64, f64)>,
+    alpha_km: f64,
+) -> Vec<Vec<(f64, f64)>> {
+    let projection = AzimuthalEquidistant::around(&points);
+    let projected: Vec<Point> = points
+        .iter()
+        .map(|p| {
+            let (north, east) = projection.project(*p);
+            Point { x: east, y: north }
+        })
+        .collect();
+    let triangulation = triangulate(&projected);
+
+    let triangle_count = triangulation.triangles.len() / 3;
+    let kept: Vec<bool> = (0..triangle_count)
+        .map(|t| {
+            let [a, b, c] = [0, 1, 2].map(|i| &projected[triangulation.triangles[3 * t + i]]);
+            circumradius(a, b, c) <= alpha_km
+        })
+        .collect();
+    if !kept.contains(&true) {
+        return vec![compute_convex_hull_of_points(points)];
+    }
+
+    // Edges of kept triangles that no other kept triangle shares, keyed by
+    // their start vertex. Outer rings run the same way round as the triangles.
+    let mut boundary: HashMap<usize, Vec<usize>> = HashMap::new();
+    for edge in 0..triangulation.triangles.len() {
+        if !kept[edge / 3] {
+            continue;
+        }
+        let twin = triangulation.halfedges[edge];
+        if twin != EMPTY && kept[twin / 3] {
+            continue;
+        }
+        let start = triangulation.triangles[edge];
+        let end = triangulation.triangles[if edge % 3 == 2 { edge - 2 } else { edge + 1 }];
+        boundary.entry(start).or_default().push(end);
+    }
+
+    let mut rings: Vec<Vec<usize>> = vec![];
+    let mut starts: Vec<usize> = boundary.keys().copied().collect();
+    starts.sort_unstable();
+    for start in starts {
+        while let Some(mut next) = boundary.get_mut(&start).and_then(|ends| ends.pop()) {
+            let mut ring = vec![start];
+            while next != start {
+                ring.push(next);
+                match boundary.get_mut(&next).and_then(|ends| ends.pop()) {
+                    Some(end) => next = end,
+                    None => break,
+                }
+            }
+            rings.push(ring);
+        }
+    }
+
+    let first_kept = kept.iter().position(|k| *k).unwrap_or(0);
+    let orientation = signed_area(
+        &triangulation.triangles[3 * first_kept..3 * first_kept + 3],
+        &projected,
+    );
+    rings
+        .into_iter()
+        .filter(|ring| signed_area(ring, &projected) * orientation > 0.0)
+        .map(|ring| ring.into_iter().map(|idx| points[idx]).collect())
+        .collect()
+}
+
+fn circumradius(a: &Point, b: &Point, c: &Point) -> f64 {
+    let ab = (b.x - a.x).hypot(b.y - a.y);
+    let bc = (c.x - b.x).hypot(c.y - b.y);
+    let ca = (a.x - c.x).hypot(a.y - c.y);
+    let twice_area = ((b.x - a.x) * (c.y - a.y) - (c.x - a.x) * (b.y - a.y)).abs();
+    if twice_area == 0.0 {
+        return f64::INFINITY;
+    }
+    ab * bc * ca / (2.0 * twice_area)
+}
+
+/// Positive for counterclockwise rings, negative for clockwise ones.
+fn signed_area(ring: &[usize], points: &[Point]) -> f64 {
+    ring.iter()
+        .zip(ring.iter().cycle().skip(1))
+        .map(|(a, b)| points[*a].x * points[*b].y - points[*b].x * points[*a].y)
+        .sum::<f64>()
+        / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        projection::polygon_area_km2,
+        ualf::{strike_near, TEST_ORIGIN},
+    };
+
+    // Strikes every 2 km over the given ranges of km north and east
+    fn grid(north_km: (i32, i32), east_km: (i32, i32)) -> Vec<UalfData> {
+        (north_km.0..=north_km.1)
+            .step_by(2)
+            .flat_map(|north| {
+                (east_km.0..=east_km.1)
+                    .step_by(2)
+                    .map(move |east| strike_near(0, north as f64, east as f64))
+            })
+            .collect()
+    }
+
+    fn area_km2(polygons: &[Vec<(f64, f64)>]) -> f64 {
+        polygons
+            .iter()
+            .map(|polygon| polygon_area_km2(polygon))
+            .sum()
+    }
+
+    fn distance_from_origin_km(point: (f64, f64)) -> f64 {
+        let (north, east) = AzimuthalEquidistant::new(TEST_ORIGIN).project(point);
+        north.hypot(east)
+    }
+
+    #[test]
+    fn follows_an_l_shaped_storm() {
+        let mut strikes = grid((0, 20), (0, 4));
+        strikes.extend(grid((0, 4), (6, 20)));
+
+        let concave = compute_concave_hull(strikes.clone(), 5.0);
+        assert_eq!(concave.len(), 1);
+        let convex = compute_convex_hull_of_points(
+            strikes.iter().map(|p| (p.latitude, p.longitude)).collect(),
+        );
+        // 20 x 4 and 16 x 4 km arms, with the inside corner rounded off,
+        // against a convex hull of about 272 km²
+        let concave_area = area_km2(&concave);
+        assert!(
+            concave_area > 144.0 && concave_area < 160.0,
+            "{}",
+            concave_area
+        );
+        assert!(concave_area < 0.6 * polygon_area_km2(&convex));
+    }
+
+    #[test]
+    fn separate_blobs_become_separate_polygons() {
+        let mut strikes = grid((0, 4), (0, 4));
+        strikes.extend(grid((50, 54), (0, 4)));
+
+        let hull = compute_concave_hull(strikes, 5.0);
+        assert_eq!(hull.len(), 2);
+        for polygon in &hull {
+            assert!((polygon_area_km2(polygon) - 16.0).abs() < 0.5);
+        }
+    }
+
+    #[test]
+    fn a_ring_of_strikes_has_no_hole() {
+        let strikes: Vec<UalfData> = (0..60)
+            .flat_map(|i| {
+                let angle = (i as f64 * 6.0).to_radians();
+                [18.0, 20.0]
+                    .map(|radius| strike_near(0, radius * angle.sin(), radius * angle.cos()))
+            })
+            .collect();
+
+        let hull = compute_concave_hull(strikes, 5.0);
+        assert_eq!(hull.len(), 1);
+        // Only the outer ring, the inner one bounds the filled hole
+        assert_eq!(hull[0].len(), 60);
+        for vertex in &hull[0] {
+            assert!((distance_from_origin_km(*vertex) - 20.0).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn falls_back_to_the_convex_hull() {
+        let pair = vec![(60.0, 10.0), (60.1, 10.0)];
+        assert_eq!(
+            compute_concave_hull_of_points(pair.clone(), 5.0),
+            vec![pair]
+        );
+
+        let line: Vec<(f64, f64)> = (0..5).map(|i| (60.0 + i as f64 * 0.01, 10.0)).collect();
+        assert_eq!(
+            compute_concave_hull_of_points(line.clone(), 5.0),
+            vec![compute_convex_hull_of_points(line)]
+        );
+
+        // Every triangle is wider than alpha
+        let sparse: Vec<(f64, f64)> = grid((0, 40), (0, 40))
+            .iter()
+            .step_by(50)
+            .map(|p| (p.latitude, p.longitude))
+            .collect();
+        assert_eq!(
+            compute_concave_hull_of_points(sparse.clone(), 1.0),
+            vec![compute_convex_hull_of_points(sparse)]
+        );
+    }
+}
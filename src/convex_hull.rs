@@ -1,19 +1,37 @@
 use std::cmp::Ordering;
-use crate::ualf::UalfData;
+use crate::{projection::AzimuthalEquidistant, ualf::UalfData};
 
 pub fn compute_convex_hull(points: Vec<UalfData>) -> Vec<(f64, f64)> {
+    // Convert to (lat, lon) pairs
+    let points: Vec<(f64, f64)> = points.iter()
+        .map(|p| (p.latitude, p.longitude))
+        .collect();
+
+    compute_convex_hull_of_points(points)
+}
+
+pub fn compute_convex_hull_of_points(points: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
     if points.len() < 3 {
-        return points.iter()
-                .map(|p| (p.latitude, p.longitude))
-                .collect();
+        return points;
     }
 
-    // Convert to (lat, lon) pairs
-    let mut points: Vec<(f64, f64)> = points.iter()
-        .map(|p| (p.latitude, p.longitude))
+    // Degrees of longitude and latitude differ in length, so work in kilometers
+    let projection = AzimuthalEquidistant::around(&points);
+    let projected: Vec<(f64, f64)> = points.iter()
+        .map(|p| projection.project(*p))
         .collect();
 
-    // Find the point with the lowest latitude (and leftmost if tied)
+    // Hull vertices are input points, returned exactly rather than unprojected
+    graham_scan(projected.clone()).into_iter()
+        .filter_map(|p| projected.iter().position(|q| *q == p))
+        .map(|idx| points[idx])
+        .collect()
+}
+
+/// Computes the convex hull of planar (north, east) points, counterclockwise
+/// from the southernmost one.
+fn graham_scan(mut points: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+    // Find the southernmost point (and westernmost if tied)
     let mut bottom_idx = 0;
     for (i, point) in points.iter().enumerate().skip(1) {
         if point.0 < points[bottom_idx].0 || 
@@ -66,4 +84,85 @@ pub fn compute_convex_hull(points: Vec<UalfData>) -> Vec<(f64, f64)> {
     }
 
     hull
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    // How far outside the hull a point may lie through rounding alone, in km
+    const TOLERANCE_KM: f64 = 1e-6;
+
+    /// The distance of `point` to the left of the directed edge from `a` to
+    /// `b`, in the projected plane. Counterclockwise hulls have every point to
+    /// the left of every edge.
+    fn left_of(a: (f64, f64), b: (f64, f64), point: (f64, f64)) -> f64 {
+        let (edge_east, edge_north) = (b.1 - a.1, b.0 - a.0);
+        let cross = edge_east * (point.0 - a.0) - edge_north * (point.1 - a.1);
+        cross / edge_east.hypot(edge_north)
+    }
+
+    fn assert_covers(points: &[(f64, f64)], hull: &[(f64, f64)]) {
+        let projection = AzimuthalEquidistant::around(points);
+        let hull: Vec<(f64, f64)> = hull.iter().map(|p| projection.project(*p)).collect();
+        for point in points {
+            let point = projection.project(*point);
+            for (a, b) in hull.iter().zip(hull.iter().cycle().skip(1)) {
+                assert!(left_of(*a, *b, point) >= -TOLERANCE_KM,
+                        "{:?} lies outside the hull {:?}", point, hull);
+            }
+        }
+    }
+
+    #[test]
+    fn every_point_lies_inside_or_on_the_hull() {
+        let mut rng = StdRng::seed_from_u64(21);
+        for _ in 0..200 {
+            let latitude = rng.gen_range(-75.0..75.0);
+            let longitude = rng.gen_range(-170.0..170.0);
+            let spread = rng.gen_range(0.01..1.5);
+            let count = rng.gen_range(3..60);
+            let points: Vec<(f64, f64)> = (0..count)
+                .map(|_| (latitude + rng.gen_range(-spread..spread),
+                          longitude + rng.gen_range(-spread..spread) * 2.0))
+                .collect();
+
+            let hull = compute_convex_hull_of_points(points.clone());
+            assert!(hull.len() >= 3 && hull.len() <= points.len());
+            assert!(hull.iter().all(|vertex| points.contains(vertex)));
+            assert_covers(&points, &hull);
+        }
+    }
+
+    #[test]
+    fn every_point_lies_inside_or_on_the_hull_far_north() {
+        let mut rng = StdRng::seed_from_u64(69);
+        for _ in 0..200 {
+            let points: Vec<(f64, f64)> = (0..50)
+                .map(|_| (69.0 + rng.gen_range(-0.5..0.5), 18.0 + rng.gen_range(-1.5..1.5)))
+                .collect();
+            assert_covers(&points, &compute_convex_hull_of_points(points.clone()));
+        }
+    }
+
+    #[test]
+    fn hull_of_a_square_with_inner_points_is_its_corners() {
+        let corners = [(69.0, 18.0), (69.0, 19.0), (69.5, 19.0), (69.5, 18.0)];
+        let mut points = vec![(69.2, 18.3), (69.25, 18.5), (69.4, 18.9)];
+        points.extend(corners);
+
+        let hull = compute_convex_hull_of_points(points.clone());
+        assert_eq!(hull.len(), 4);
+        assert!(corners.iter().all(|corner| hull.contains(corner)));
+        assert_covers(&points, &hull);
+    }
+
+    #[test]
+    fn fewer_than_three_points_are_their_own_hull() {
+        assert!(compute_convex_hull_of_points(vec![]).is_empty());
+        assert_eq!(compute_convex_hull_of_points(vec![(60.0, 10.0), (60.1, 10.0)]),
+                   vec![(60.0, 10.0), (60.1, 10.0)]);
+    }
+}
@@ -4,7 +4,13 @@ use log::{error, info};
 use postgrest::Postgrest;
 use serde::{Deserialize, Serialize};
 
-use crate::dbscan::DbscanCluster;
+use crate::{
+    alert_state::AlertState,
+    dbscan::{HullParams, StormMetrics},
+    geojson::{Feature, FeatureCollection},
+    nowcast::nowcast_storm_at_lead_times,
+    storm_tracker::{StormEvent, TrackedStorm},
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UserLocation {
@@ -13,6 +19,8 @@ pub struct UserLocation {
     pub latitude: f64,
     pub longitude: f64,
     pub radius_km: i16,
+    #[serde(default)]
+    pub all_clear_minutes: Option<i16>, // Defaults to the 30-minute rule
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -26,18 +34,81 @@ pub struct Observation {
     pub location_id: i64,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LocationAlert {
+    pub location_id: i64,
+    pub state: AlertState,
+    pub last_strike_epoch_ns: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlertTransition {
+    pub location_id: i64,
+    pub from_state: AlertState,
+    pub to_state: AlertState,
+    pub epoch_ns: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LocationForecast {
+    pub location_id: i64,
+    pub storm_id: Option<i64>, // The storm arriving first, or else the likeliest
+    pub eta_minutes: Option<i64>, // None unless a storm is on course within the hour
+    pub probability: f64,      // That any storm enters the radius within the hour
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Prediction {
     pub id: i64,
     pub created_at: String,
 }
 #[derive(Debug, Serialize, Deserialize)]
-pub struct PredictionInput {}
+pub struct PredictionInput {
+    pub storms: FeatureCollection, // JSONB, every row of the prediction
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ClusterLocationInput {
     pub prediction_id: i64,
-    pub location: String, // JSONB
+    pub storm_id: i64,
+    pub location: Feature,      // JSONB
+    pub lead_time_minutes: i64, // 0 for the observed hull, otherwise a nowcast
+    pub speed_kmh: Option<f64>,
+    pub heading_deg: Option<f64>, // Direction of travel, clockwise from north
+    #[serde(flatten)]
+    pub metrics: StormMetrics, // Without hull measures on nowcasts
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StormEventInput {
+    pub storm_id: i64,
+    pub kind: String, // "merge" or "split"
+    pub related_storm_ids: Vec<i64>,
+}
+
+impl StormEventInput {
+    pub fn new(event: &StormEvent) -> StormEventInput {
+        match event {
+            StormEvent::Merge { storm_id, merged } => StormEventInput {
+                storm_id: *storm_id,
+                kind: "merge".to_string(),
+                related_storm_ids: merged.clone(),
+            },
+            StormEvent::Split {
+                storm_id,
+                split_into,
+            } => StormEventInput {
+                storm_id: *storm_id,
+                kind: "split".to_string(),
+                related_storm_ids: split_into.clone(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StormIdRow {
+    pub storm_id: i64,
 }
 
 pub struct Database {
@@ -60,18 +131,18 @@ impl Clone for Database {
 
 impl Database {
     pub fn init(supabase_url: &str, supabase_api: &str) -> Database {
-        return Database {
+        Database {
             client: Postgrest::new(supabase_url)
                 .insert_header("apikey", supabase_api)
                 .insert_header("Authorization", format!("Bearer {}", supabase_api)),
             base_url: supabase_url.to_string(),
             api_key: supabase_api.to_string(),
-        };
+        }
     }
 
     pub async fn insert_observations(
         &self,
-        observations: Vec<Observation>,
+        observations: &[Observation],
     ) -> Result<(), Box<dyn Error>> {
         let json_observations = match serde_json::to_string(&observations) {
             Ok(json_str) => json_str,
@@ -92,11 +163,197 @@ impl Database {
             Ok(_) => Ok(()),
             Err(err) => {
                 error!("Unable to write observations to db: {}", err);
+                Err(err.into())
+            }
+        }
+    }
+
+    pub async fn insert_storm_events(&self, events: &[StormEvent]) -> Result<(), Box<dyn Error>> {
+        let storm_events: Vec<StormEventInput> = events.iter().map(StormEventInput::new).collect();
+        let json_storm_events = match serde_json::to_string(&storm_events) {
+            Ok(json_str) => json_str,
+            Err(err) => {
+                error!("Unable to serialize storm events: {}", err);
+                return Err(err.into());
+            }
+        };
+
+        let response = self
+            .client
+            .from("storm_events")
+            .insert(&json_storm_events)
+            .execute()
+            .await;
+
+        match response {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                error!("Unable to write storm events to db: {}", err);
+                Err(err.into())
+            }
+        }
+    }
+
+    /// Replaces the forecast of each location.
+    pub async fn upsert_location_forecasts(
+        &self,
+        forecasts: Vec<LocationForecast>,
+    ) -> Result<(), Box<dyn Error>> {
+        let json_forecasts = match serde_json::to_string(&forecasts) {
+            Ok(json_str) => json_str,
+            Err(err) => {
+                error!("Unable to serialize location forecasts: {}", err);
+                return Err(err.into());
+            }
+        };
+
+        let response = self
+            .client
+            .from("location_forecasts")
+            .upsert(&json_forecasts)
+            .on_conflict("location_id")
+            .execute()
+            .await;
+
+        match response {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                error!("Unable to write location forecasts to db: {}", err);
+                Err(err.into())
+            }
+        }
+    }
+
+    pub async fn get_location_alerts(&self) -> Result<Vec<LocationAlert>, Box<dyn Error>> {
+        let response_result = self
+            .client
+            .from("location_alerts")
+            .select('*')
+            .execute()
+            .await;
+
+        let response = match response_result {
+            Ok(res) => res,
+            Err(err) => {
+                error!("Unable to get location alerts: {}", err);
+                return Err(err.into());
+            }
+        };
+
+        let response_text = match response.text().await {
+            Ok(text) => text,
+            Err(err) => {
+                error!("Unable to get response string: {}", err);
                 return Err(err.into());
             }
+        };
+
+        match serde_json::from_str(&response_text) {
+            Ok(alerts) => Ok(alerts),
+            Err(err) => {
+                error!("Unable to deserialize location alerts: {}", err);
+                Err(err.into())
+            }
         }
     }
 
+    /// Persists the current alert of each location and the transitions that led to it.
+    pub async fn update_location_alerts(
+        &self,
+        alerts: Vec<LocationAlert>,
+        transitions: Vec<AlertTransition>,
+    ) -> Result<(), Box<dyn Error>> {
+        let json_alerts = match serde_json::to_string(&alerts) {
+            Ok(json_str) => json_str,
+            Err(err) => {
+                error!("Unable to serialize location alerts: {}", err);
+                return Err(err.into());
+            }
+        };
+
+        let response = self
+            .client
+            .from("location_alerts")
+            .upsert(&json_alerts)
+            .on_conflict("location_id")
+            .execute()
+            .await;
+
+        if let Err(err) = response {
+            error!("Unable to write location alerts to db: {}", err);
+            return Err(err.into());
+        }
+
+        if transitions.is_empty() {
+            return Ok(());
+        }
+
+        let json_transitions = match serde_json::to_string(&transitions) {
+            Ok(json_str) => json_str,
+            Err(err) => {
+                error!("Unable to serialize alert transitions: {}", err);
+                return Err(err.into());
+            }
+        };
+
+        let response = self
+            .client
+            .from("alert_transitions")
+            .insert(&json_transitions)
+            .execute()
+            .await;
+
+        match response {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                error!("Unable to write alert transitions to db: {}", err);
+                Err(err.into())
+            }
+        }
+    }
+
+    async fn get_max_storm_id_in(&self, table: &str) -> Result<Option<i64>, Box<dyn Error>> {
+        let response_result = self
+            .client
+            .from(table)
+            .select("storm_id")
+            .order("storm_id.desc")
+            .limit(1)
+            .execute()
+            .await;
+
+        let response = match response_result {
+            Ok(res) => res,
+            Err(err) => {
+                error!("Unable to get storm IDs from {}: {}", table, err);
+                return Err(err.into());
+            }
+        };
+
+        let response_text = match response.text().await {
+            Ok(text) => text,
+            Err(err) => {
+                error!("Unable to get response string: {}", err);
+                return Err(err.into());
+            }
+        };
+
+        match serde_json::from_str::<Vec<StormIdRow>>(&response_text) {
+            Ok(rows) => Ok(rows.first().map(|row| row.storm_id)),
+            Err(err) => {
+                error!("Unable to deserialize storm IDs from {}: {}", table, err);
+                Err(err.into())
+            }
+        }
+    }
+
+    /// The highest storm ID stored by any run, so new storms never reuse one.
+    pub async fn get_max_storm_id(&self) -> Result<Option<i64>, Box<dyn Error>> {
+        let in_predictions = self.get_max_storm_id_in("cluster_locations").await?;
+        let in_events = self.get_max_storm_id_in("storm_events").await?;
+        Ok(in_predictions.max(in_events))
+    }
+
     pub async fn get_locations(&self) -> Result<Vec<UserLocation>, Box<dyn Error>> {
         let response_result = self.client.from("locations").select('*').execute().await;
 
@@ -123,16 +380,57 @@ impl Database {
             Ok(locations) => Ok(locations),
             Err(err) => {
                 error!("Unable to deserialize locations: {}", err);
-                return Err(err.into());
+                Err(err.into())
             }
         }
     }
 
+    /// Stores the storms as the current prediction, `hulls` holding the hull
+    /// of each storm from `DbscanCluster::hull`.
     pub async fn insert_prediction_and_remove_old(
         &self,
-        clusters: Vec<DbscanCluster>,
+        storms: Vec<TrackedStorm>,
+        hulls: &[Vec<Vec<(f64, f64)>>],
+        params: &HullParams,
     ) -> Result<(), Box<dyn Error>> {
-        let new_prediction = PredictionInput {};
+        // Rows are built before the prediction exists, then given its ID
+        let mut cluster_locations: Vec<ClusterLocationInput> = vec![];
+        for (storm, hull) in storms.iter().zip(hulls) {
+            let speed_kmh = storm.motion.map(|motion| motion.speed_kmh());
+            let heading_deg = storm.motion.map(|motion| motion.heading_deg());
+            let metrics = storm.cluster.metrics(hull);
+            let outline = params.algorithm.buffer(hull, params.buffer_km);
+            cluster_locations.push(ClusterLocationInput {
+                prediction_id: 0,
+                storm_id: storm.storm_id,
+                location: Feature::storm(storm, &outline, metrics),
+                lead_time_minutes: 0,
+                speed_kmh,
+                heading_deg,
+                metrics,
+            });
+            let metrics = metrics.for_nowcast();
+            for nowcast in nowcast_storm_at_lead_times(storm, hull, params) {
+                cluster_locations.push(ClusterLocationInput {
+                    prediction_id: 0,
+                    storm_id: storm.storm_id,
+                    location: Feature::nowcast(storm, &nowcast, metrics),
+                    lead_time_minutes: (nowcast.lead_time.as_secs() / 60) as i64,
+                    speed_kmh,
+                    heading_deg,
+                    metrics,
+                });
+            }
+        }
+
+        let new_prediction = PredictionInput {
+            storms: FeatureCollection {
+                features: cluster_locations
+                    .iter()
+                    .map(|cluster_location| cluster_location.location.clone())
+                    .collect(),
+            },
+        };
         let json_new_prediction = serde_json::to_string(&new_prediction).unwrap();
 
         let prediction_result = self
@@ -171,12 +469,8 @@ impl Database {
             }
         };
 
-        let mut cluster_locations: Vec<ClusterLocationInput> = vec![];
-        for cluster in clusters {
-            cluster_locations.push(ClusterLocationInput {
-                prediction_id: prediction.id,
-                location: cluster.convex_hull_geo_json(),
-            });
+        for cluster_location in &mut cluster_locations {
+            cluster_location.prediction_id = prediction.id;
         }
 
         let json_cluster_locations = match serde_json::to_string(&cluster_locations) {
@@ -214,7 +508,7 @@ impl Database {
             Ok(_) => Ok(()),
             Err(err) => {
                 error!("Unable to remove old predictions: {}", err);
-                return Err(err.into());
+                Err(err.into())
             }
         }
     }
@@ -1,7 +1,14 @@
-use geoutils::Location;
-use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
 
-use crate::{convex_hull::compute_convex_hull, ualf::UalfData};
+use crate::{
+    concave_hull::compute_concave_hull,
+    convex_hull::compute_convex_hull,
+    hull_buffer::{buffer_concave_polygons, buffer_convex_polygon},
+    projection::{distance_km, polygon_area_km2, polygon_perimeter_km},
+    ualf::UalfData,
+};
 
 #[derive(Debug)]
 pub struct DbscanCluster {
@@ -9,93 +16,279 @@ pub struct DbscanCluster {
     pub cluster_id: usize,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PointLabel {
+    Core,   // Has at least min_points neighbours
+    Border, // Within eps of a core point, but not a core point itself
+    Noise,  // Not reachable from any core point
+}
+
+/// The clusters found by `cluster_lightning`, along with the strikes that
+/// belong to none of them. `labels` follows the order of the input points.
+#[derive(Debug)]
+pub struct DbscanResult {
+    pub clusters: Vec<DbscanCluster>,
+    pub noise: Vec<UalfData>,
+    pub labels: Vec<PointLabel>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NeighborIndex {
+    Grid,       // Only points in adjacent grid cells are compared
+    BruteForce, // Every point is compared, O(n²)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HullAlgorithm {
+    Convex,                    // Graham's scan, covers every strike
+    Concave { alpha_km: f64 }, // Alpha shape, follows the shape of the storm
+}
+
+impl HullAlgorithm {
+    /// Parses `convex`, or `concave` with an optional alpha radius in
+    /// kilometers such as `concave:10`.
+    pub fn parse(hull_str: &str) -> Option<HullAlgorithm> {
+        let (name, alpha_km) = match hull_str.trim().split_once(':') {
+            Some((name, alpha_km)) => (name, Some(alpha_km.trim().parse::<f64>().ok()?)),
+            None => (hull_str.trim(), None),
+        };
+        match (name.to_lowercase().as_str(), alpha_km) {
+            ("convex", None) => Some(HullAlgorithm::Convex),
+            ("concave", alpha_km) => Some(HullAlgorithm::Concave {
+                alpha_km: alpha_km.unwrap_or(DEFAULT_ALPHA_KM),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Grows a hull from `DbscanCluster::hull` by `distance_km` in every
+    /// direction, keeping it convex or concave.
+    pub fn buffer(&self, hull: &[Vec<(f64, f64)>], distance_km: f64) -> Vec<Vec<(f64, f64)>> {
+        match self {
+            HullAlgorithm::Convex => hull
+                .iter()
+                .map(|polygon| buffer_convex_polygon(polygon, distance_km))
+                .collect(),
+            HullAlgorithm::Concave { alpha_km } => {
+                buffer_concave_polygons(hull, distance_km, *alpha_km)
+            }
+        }
+    }
+}
+
+/// How a storm is outlined for storage.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HullParams {
+    pub algorithm: HullAlgorithm,
+    pub buffer_km: f64, // Safety margin around the outermost strikes
+}
+
+impl Default for HullParams {
+    fn default() -> Self {
+        HullParams {
+            algorithm: HullAlgorithm::Convex,
+            buffer_km: 10.0,
+        }
+    }
+}
+
+/// Measures of a storm's size and severity, persisted with each cluster.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StormMetrics {
+    pub area_km2: Option<f64>,         // Of the hull, None on nowcasts
+    pub perimeter_km: Option<f64>,     // Of the hull, None on nowcasts
+    pub flash_density: Option<f64>,    // Strikes per km² per minute, None for a zero area
+    pub max_current_ka: i16,           // Largest in magnitude, sign gives polarity
+    pub polarity_ratio: Option<f64>,   // Positive per negative strike, None without negatives
+    pub cloud_to_ground_fraction: f64, // Share of strikes not flagged as cloud lightning
+}
+
+impl StormMetrics {
+    /// The measures that still hold for a nowcast. Its outline is moved and
+    /// widened, so the observed hull's area, perimeter and density do not.
+    pub fn for_nowcast(self) -> StormMetrics {
+        StormMetrics {
+            area_km2: None,
+            perimeter_km: None,
+            flash_density: None,
+            ..self
+        }
+    }
+}
+
 pub struct DbscanParams {
-    pub eps_km: f64,       // Maximum distance between points in kilometers
-    pub min_points: usize, // Minimum points to form a cluster
+    pub eps_km: f64,                // Maximum distance between points in kilometers
+    pub eps_time: Option<Duration>, // Maximum time between points, None to ignore time
+    pub min_points: usize,          // Minimum neighbours, itself included, of a core point
+    pub index: NeighborIndex,       // How neighbours are looked up
 }
 
 impl Default for DbscanParams {
     fn default() -> Self {
         DbscanParams {
             eps_km: 10.0,
+            eps_time: Some(Duration::from_secs(15 * 60)),
             min_points: 3,
+            index: NeighborIndex::Grid,
         }
     }
 }
 
-pub fn cluster_lightning(data: &[UalfData], params: &DbscanParams) -> Vec<DbscanCluster> {
-    let mut clusters: Vec<DbscanCluster> = Vec::new();
-    let mut visited: HashSet<usize> = HashSet::new();
-    let mut point_to_cluster: HashMap<usize, usize> = HashMap::new();
-    let mut current_cluster_id = 0;
-
-    // Find neighbors within eps_km radius
-    fn get_neighbors(
-        point_idx: usize,
-        data: &[UalfData],
-        eps_km: f64,
-        visited: &HashSet<usize>,
-    ) -> Vec<usize> {
-        let mut neighbors = Vec::new();
-        let point = &data[point_idx];
-
-        for (idx, other) in data.iter().enumerate() {
-            if visited.contains(&idx) {
-                continue;
-            }
-            let point_loc = Location::new(point.latitude, point.longitude);
-            let other_loc = Location::new(other.latitude, other.longitude);
-            let distance = point_loc.haversine_distance_to(&other_loc);
+// Wide enough that strikes one eps apart still form triangles
+const DEFAULT_ALPHA_KM: f64 = 10.0;
 
-            if (distance.meters() / 1000.0) <= eps_km {
-                neighbors.push(idx);
-            }
+// Kilometers per degree of latitude on the sphere of `projection::distance_km`,
+// rounded down so grid cells are never narrower than eps
+const KM_PER_DEGREE: f64 = 111.0;
+
+/// Buckets points into cells at least `eps_km` wide, so every neighbour of a
+/// point lies in the 3x3 block of cells around it.
+struct SpatialGrid {
+    cell_lat: f64,
+    cell_lon: f64,
+    cells: HashMap<(i64, i64), Vec<usize>>,
+}
+
+impl SpatialGrid {
+    fn new(data: &[UalfData], eps_km: f64) -> SpatialGrid {
+        let cell_lat = eps_km / KM_PER_DEGREE;
+        // Longitude degrees shrink towards the poles, so size cells for the
+        // highest latitude a neighbour can be at
+        let max_abs_lat = data.iter().map(|p| p.latitude.abs()).fold(0.0, f64::max);
+        let widest_lat = (max_abs_lat + cell_lat).min(89.0);
+        let cell_lon = eps_km / (KM_PER_DEGREE * widest_lat.to_radians().cos());
+
+        let mut grid = SpatialGrid {
+            cell_lat,
+            cell_lon,
+            cells: HashMap::new(),
+        };
+        for (idx, point) in data.iter().enumerate() {
+            let cell = grid.cell(point);
+            grid.cells.entry(cell).or_default().push(idx);
+        }
+        grid
+    }
+
+    fn cell(&self, point: &UalfData) -> (i64, i64) {
+        (
+            (point.latitude / self.cell_lat).floor() as i64,
+            (point.longitude / self.cell_lon).floor() as i64,
+        )
+    }
+
+    fn candidates(&self, point: &UalfData) -> impl Iterator<Item = usize> + '_ {
+        let (row, col) = self.cell(point);
+        (row - 1..=row + 1)
+            .flat_map(move |r| (col - 1..=col + 1).map(move |c| (r, c)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+    }
+}
+
+/// Finds the points within `eps_km` and, if set, `eps_time` of `point_idx`,
+/// including the point itself, in index order.
+fn get_neighbors(
+    point_idx: usize,
+    data: &[UalfData],
+    params: &DbscanParams,
+    grid: Option<&SpatialGrid>,
+) -> Vec<usize> {
+    let point = &data[point_idx];
+    let eps_ns = params.eps_time.map(|eps| eps.as_nanos() as i64);
+    let is_neighbor = |idx: &usize| {
+        let other = &data[*idx];
+        if eps_ns.is_some_and(|eps_ns| (point.epoch_ns - other.epoch_ns).abs() > eps_ns) {
+            return false;
         }
+        let distance = distance_km(
+            (point.latitude, point.longitude),
+            (other.latitude, other.longitude),
+        );
+        distance <= params.eps_km
+    };
 
-        neighbors
+    match grid {
+        Some(grid) => {
+            // Keep index order so clusters match the brute force search
+            let mut neighbors: Vec<usize> = grid.candidates(point).filter(is_neighbor).collect();
+            neighbors.sort_unstable();
+            neighbors
+        }
+        None => (0..data.len()).filter(is_neighbor).collect(),
     }
+}
+
+/// Groups strikes with DBSCAN. A point is a core point when at least
+/// `min_points` points, itself included, lie within eps of it. Border points
+/// join the first cluster that reaches them and everything else is noise.
+pub fn cluster_lightning(data: &[UalfData], params: &DbscanParams) -> DbscanResult {
+    let mut clusters: Vec<DbscanCluster> = Vec::new();
+    let mut labels = vec![PointLabel::Noise; data.len()];
+    let mut visited = vec![false; data.len()];
+    let mut assigned = vec![false; data.len()];
+    let grid = match params.index {
+        NeighborIndex::Grid => Some(SpatialGrid::new(data, params.eps_km)),
+        NeighborIndex::BruteForce => None,
+    };
 
-    // Main DBSCAN algorithm
     for point_idx in 0..data.len() {
-        if visited.contains(&point_idx) {
+        if visited[point_idx] {
+            continue;
+        }
+        visited[point_idx] = true;
+
+        let neighbors = get_neighbors(point_idx, data, params, grid.as_ref());
+        if neighbors.len() < params.min_points {
+            // Noise unless a later cluster reaches it as a border point
             continue;
         }
 
-        visited.insert(point_idx);
-        let neighbors = get_neighbors(point_idx, data, params.eps_km, &visited);
-
-        if neighbors.len() >= params.min_points {
-            // Start a new cluster
-            let mut cluster = DbscanCluster {
-                points: vec![data[point_idx].clone()],
-                cluster_id: current_cluster_id,
-            };
-            point_to_cluster.insert(point_idx, current_cluster_id);
-
-            // Process neighbors
-            let mut neighbor_queue = neighbors;
-            while let Some(neighbor_idx) = neighbor_queue.pop() {
-                if !visited.contains(&neighbor_idx) {
-                    visited.insert(neighbor_idx);
-                    let new_neighbors = get_neighbors(neighbor_idx, data, params.eps_km, &visited);
-
-                    if new_neighbors.len() >= params.min_points {
-                        neighbor_queue.extend(new_neighbors);
-                    }
-                }
-
-                if !point_to_cluster.contains_key(&neighbor_idx) {
-                    cluster.points.push(data[neighbor_idx].clone());
-                    point_to_cluster.insert(neighbor_idx, current_cluster_id);
-                }
+        let mut cluster = DbscanCluster {
+            points: vec![],
+            cluster_id: clusters.len(),
+        };
+        labels[point_idx] = PointLabel::Core;
+        assigned[point_idx] = true;
+        cluster.points.push(data[point_idx].clone());
+
+        // Expand the cluster through every core point density-reachable from here
+        let mut neighbor_queue = neighbors;
+        while let Some(neighbor_idx) = neighbor_queue.pop() {
+            if !assigned[neighbor_idx] {
+                labels[neighbor_idx] = PointLabel::Border;
+                assigned[neighbor_idx] = true;
+                cluster.points.push(data[neighbor_idx].clone());
+            }
+            if visited[neighbor_idx] {
+                continue;
             }
+            visited[neighbor_idx] = true;
 
-            clusters.push(cluster);
-            current_cluster_id += 1;
+            let new_neighbors = get_neighbors(neighbor_idx, data, params, grid.as_ref());
+            if new_neighbors.len() >= params.min_points {
+                labels[neighbor_idx] = PointLabel::Core;
+                // Visited noise is unassigned too, and becomes a border point here
+                neighbor_queue.extend(new_neighbors.into_iter().filter(|idx| !assigned[*idx]));
+            }
         }
+
+        clusters.push(cluster);
     }
 
-    clusters
+    let noise = data
+        .iter()
+        .zip(&labels)
+        .filter(|(_, label)| **label == PointLabel::Noise)
+        .map(|(point, _)| point.clone())
+        .collect();
+
+    DbscanResult {
+        clusters,
+        noise,
+        labels,
+    }
 }
 
 // Example usage and helper functions
@@ -122,23 +315,331 @@ impl DbscanCluster {
         max_time - min_time
     }
 
-    pub fn convex_hull_geo_json(&self) -> String {
+    /// The peak current largest in magnitude, 0 for an empty cluster.
+    pub fn max_current(&self) -> i16 {
+        self.points
+            .iter()
+            .map(|p| p.peak_current)
+            .max_by_key(|current| current.unsigned_abs())
+            .unwrap_or(0)
+    }
+
+    /// Positive strikes per negative one, None when there are no negative ones.
+    pub fn polarity_ratio(&self) -> Option<f64> {
+        let positive = self.points.iter().filter(|p| p.peak_current > 0).count();
+        let negative = self.points.iter().filter(|p| p.peak_current < 0).count();
+        if negative == 0 {
+            return None;
+        }
+        Some(positive as f64 / negative as f64)
+    }
+
+    pub fn cloud_to_ground_fraction(&self) -> f64 {
         if self.points.is_empty() {
-            return format!("[]");
-        }
-
-        let mut first = true;
-        let mut json = format!("[");
-        let convex_hull = compute_convex_hull(self.points.clone());
-        for (latitude, longitude) in convex_hull {
-            if first {
-                json.push_str(format!("[{},{}]", latitude, longitude).as_str());
-                first = false;
-            } else {
-                json.push_str(format!(",[{},{}]", latitude, longitude).as_str());
+            return 0.0;
+        }
+        let cloud_to_ground = self.points.iter().filter(|p| !p.cloud_indicator).count();
+        cloud_to_ground as f64 / self.points.len() as f64
+    }
+
+    /// The storm's hull, without the safety margin. A concave hull may have
+    /// several parts.
+    pub fn hull(&self, algorithm: HullAlgorithm) -> Vec<Vec<(f64, f64)>> {
+        if self.points.is_empty() {
+            return vec![];
+        }
+
+        match algorithm {
+            HullAlgorithm::Convex => vec![compute_convex_hull(self.points.clone())],
+            HullAlgorithm::Concave { alpha_km } => {
+                compute_concave_hull(self.points.clone(), alpha_km)
             }
         }
-        json.push(']');
-        json
+    }
+
+    /// The storm's measures, given its hull from `hull`. Area and perimeter
+    /// are geodesic, before the safety margin and summed over the hull's parts.
+    pub fn metrics(&self, hull: &[Vec<(f64, f64)>]) -> StormMetrics {
+        let area_km2 = hull.iter().map(|polygon| polygon_area_km2(polygon)).sum();
+        let perimeter_km = hull
+            .iter()
+            .map(|polygon| polygon_perimeter_km(polygon))
+            .sum();
+        StormMetrics {
+            area_km2: Some(area_km2),
+            perimeter_km: Some(perimeter_km),
+            flash_density: flash_density(self.points.len(), area_km2, self.time_span_ns()),
+            max_current_ka: self.max_current(),
+            polarity_ratio: self.polarity_ratio(),
+            cloud_to_ground_fraction: self.cloud_to_ground_fraction(),
+        }
+    }
+
+    /// The storm's outline, grown by the safety margin. A concave outline
+    /// may have several parts.
+    pub fn outline(&self, params: &HullParams) -> Vec<Vec<(f64, f64)>> {
+        params
+            .algorithm
+            .buffer(&self.hull(params.algorithm), params.buffer_km)
+    }
+}
+
+// Strikes per km² of hull per minute of activity. Storms active for less than
+// a minute are counted over one, so a burst of simultaneous strikes does not
+// read as infinitely dense.
+fn flash_density(strike_count: usize, area_km2: f64, time_span_ns: i64) -> Option<f64> {
+    if area_km2 <= 0.0 {
+        return None;
+    }
+    let minutes = (time_span_ns as f64 / 60e9).max(1.0);
+    Some(strike_count as f64 / area_km2 / minutes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+    use crate::ualf::strike_near;
+
+    const MINUTE_NS: i64 = 60 * 1_000_000_000;
+
+    // Micro-degrees of latitude and longitude
+    type Position = (i64, i64);
+
+    fn labels_by_position(data: &[UalfData], result: &DbscanResult) -> Vec<(Position, PointLabel)> {
+        let mut labels: Vec<(Position, PointLabel)> = data
+            .iter()
+            .zip(&result.labels)
+            .map(|(point, label)| {
+                let position = (
+                    (point.latitude * 1e6) as i64,
+                    (point.longitude * 1e6) as i64,
+                );
+                (position, *label)
+            })
+            .collect();
+        labels.sort_by_key(|(position, _)| *position);
+        labels
+    }
+
+    #[test]
+    fn labels_core_border_and_noise() {
+        let data = vec![
+            strike_near(0, 0.0, 0.0),
+            strike_near(0, 4.0, 0.0),
+            strike_near(0, 8.0, 0.0),
+            strike_near(0, 17.0, 0.0), // Only one neighbour, but that one is a core point
+            strike_near(0, 40.0, 0.0), // Far from everything
+        ];
+        let result = cluster_lightning(&data, &DbscanParams::default());
+
+        assert_eq!(
+            result.labels,
+            vec![
+                PointLabel::Core,
+                PointLabel::Core,
+                PointLabel::Core,
+                PointLabel::Border,
+                PointLabel::Noise
+            ]
+        );
+        assert_eq!(result.clusters.len(), 1);
+        assert_eq!(result.clusters[0].points.len(), 4);
+        assert!(result.clusters[0]
+            .points
+            .iter()
+            .any(|point| point.latitude == data[3].latitude));
+        assert_eq!(result.noise, vec![data[4].clone()]);
+    }
+
+    #[test]
+    fn noise_is_every_strike_outside_a_cluster() {
+        let data = vec![
+            strike_near(0, 0.0, 0.0),
+            strike_near(0, 1.0, 0.0),
+            strike_near(0, 0.0, 1.0),
+            strike_near(0, 30.0, 0.0),
+            strike_near(0, 35.0, 0.0), // A pair is below min_points
+            strike_near(0, 0.0, 50.0),
+        ];
+        let result = cluster_lightning(&data, &DbscanParams::default());
+
+        assert_eq!(result.clusters.len(), 1);
+        assert_eq!(result.noise, data[3..].to_vec());
+        let noise_labels = result
+            .labels
+            .iter()
+            .filter(|label| **label == PointLabel::Noise)
+            .count();
+        assert_eq!(noise_labels, result.noise.len());
+    }
+
+    #[test]
+    fn labels_do_not_depend_on_input_order() {
+        let mut rng = StdRng::seed_from_u64(15);
+        let mut data: Vec<UalfData> = (0..300)
+            .map(|i| {
+                let (north, east) = [(0.0, 0.0), (25.0, 5.0), (60.0, -20.0)][i % 3];
+                strike_near(
+                    0,
+                    north + 12.0 * (i as f64 * 0.37).sin() * (i as f64 / 300.0),
+                    east + 12.0 * (i as f64 * 0.37).cos() * (i as f64 / 300.0),
+                )
+            })
+            // Stragglers off the edge of each spiral, some in reach of a core point
+            .chain((0..30).map(|i| {
+                let (north, east) = [(0.0, 0.0), (25.0, 5.0), (60.0, -20.0)][i % 3];
+                let (distance, bearing) = (14.0 + (i / 3) as f64, i as f64);
+                strike_near(
+                    0,
+                    north + distance * bearing.sin(),
+                    east + distance * bearing.cos(),
+                )
+            }))
+            .collect();
+        let params = DbscanParams {
+            min_points: 8,
+            ..DbscanParams::default()
+        };
+        let expected = labels_by_position(&data, &cluster_lightning(&data, &params));
+        for kind in [PointLabel::Border, PointLabel::Noise] {
+            assert!(expected.iter().any(|(_, label)| *label == kind));
+        }
+
+        for _ in 0..5 {
+            data.shuffle(&mut rng);
+            let shuffled = cluster_lightning(&data, &params);
+            assert_eq!(labels_by_position(&data, &shuffled), expected);
+        }
+    }
+
+    #[test]
+    fn noise_seen_first_still_becomes_a_border_point() {
+        let data = [
+            strike_near(0, 0.0, 0.0),
+            strike_near(0, 4.0, 0.0),
+            strike_near(0, 8.0, 0.0),
+            strike_near(0, 17.0, 0.0),
+        ];
+        let expected = vec![
+            PointLabel::Core,
+            PointLabel::Core,
+            PointLabel::Core,
+            PointLabel::Border,
+        ];
+        assert_eq!(
+            cluster_lightning(&data, &DbscanParams::default()).labels,
+            expected
+        );
+
+        let reordered = [
+            data[3].clone(),
+            data[0].clone(),
+            data[1].clone(),
+            data[2].clone(),
+        ];
+        let result = cluster_lightning(&reordered, &DbscanParams::default());
+        assert_eq!(
+            result.labels,
+            vec![
+                PointLabel::Border,
+                PointLabel::Core,
+                PointLabel::Core,
+                PointLabel::Core,
+            ]
+        );
+        assert_eq!(result.clusters.len(), 1);
+        assert_eq!(result.clusters[0].points.len(), 4);
+        assert!(result.noise.is_empty());
+    }
+
+    #[test]
+    fn grid_and_brute_force_agree() {
+        let data: Vec<UalfData> = (0..200)
+            .map(|i| {
+                strike_near(
+                    i * MINUTE_NS / 10,
+                    (i % 17) as f64 * 3.0,
+                    (i % 11) as f64 * 4.0,
+                )
+            })
+            .collect();
+        let brute_force = DbscanParams {
+            index: NeighborIndex::BruteForce,
+            ..DbscanParams::default()
+        };
+        assert_eq!(
+            cluster_lightning(&data, &DbscanParams::default()).labels,
+            cluster_lightning(&data, &brute_force).labels
+        );
+    }
+
+    #[test]
+    fn eps_time_splits_two_passes_over_the_same_spot() {
+        let data: Vec<UalfData> = (0..10)
+            .map(|i| strike_near(i * MINUTE_NS, (i % 3) as f64, (i % 2) as f64))
+            .chain(
+                (0..10).map(|i| strike_near((50 + i) * MINUTE_NS, (i % 2) as f64, (i % 3) as f64)),
+            )
+            .collect();
+
+        let result = cluster_lightning(&data, &DbscanParams::default());
+        assert_eq!(result.clusters.len(), 2);
+        assert!(result.noise.is_empty());
+        for cluster in &result.clusters {
+            assert_eq!(cluster.points.len(), 10);
+            assert!(cluster.time_span_ns() <= 9 * MINUTE_NS);
+        }
+
+        let timeless = DbscanParams {
+            eps_time: None,
+            ..DbscanParams::default()
+        };
+        assert_eq!(cluster_lightning(&data, &timeless).clusters.len(), 1);
+    }
+
+    #[test]
+    fn metrics_measure_the_given_hull() {
+        let cluster = DbscanCluster {
+            points: vec![
+                strike_near(0, 0.0, 0.0),
+                strike_near(2 * MINUTE_NS, 10.0, 0.0),
+                strike_near(4 * MINUTE_NS, 5.0, 5.0),
+                strike_near(6 * MINUTE_NS, 10.0, 10.0),
+                strike_near(10 * MINUTE_NS, 0.0, 10.0),
+            ],
+            cluster_id: 0,
+        };
+        let metrics = cluster.metrics(&cluster.hull(HullAlgorithm::Convex));
+        assert!(
+            (metrics.area_km2.unwrap() - 100.0).abs() < 0.5,
+            "{}",
+            metrics.area_km2.unwrap()
+        );
+        assert!(
+            (metrics.perimeter_km.unwrap() - 40.0).abs() < 0.1,
+            "{}",
+            metrics.perimeter_km.unwrap()
+        );
+        // 5 strikes over 100 km² and 10 minutes
+        assert!((metrics.flash_density.unwrap() - 0.005).abs() < 1e-4);
+
+        // A line of strikes encloses nothing
+        let line = cluster.metrics(&[vec![(60.0, 10.0), (60.1, 10.0)]]);
+        assert_eq!(line.area_km2, Some(0.0));
+        assert_eq!(line.flash_density, None);
+
+        let nowcast = metrics.for_nowcast();
+        assert_eq!(
+            (
+                nowcast.area_km2,
+                nowcast.perimeter_km,
+                nowcast.flash_density
+            ),
+            (None, None, None)
+        );
+        assert_eq!(nowcast.max_current_ka, metrics.max_current_ka);
     }
 }
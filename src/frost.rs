@@ -1,21 +1,71 @@
 use std::fmt;
 use std::error;
 use std::error::Error;
+use std::sync::Mutex;
+use std::time::Duration;
 
-use crate::ualf::UalfData;
+use crate::circuit_breaker::{CircuitBreaker, CircuitState};
+use crate::lightning_source::{LightningSource, SourceError, SourceHealth};
+use crate::region::Region;
+use crate::ualf::{read_ualf, UalfData};
+use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
+use log::{debug, warn};
+use rand::Rng;
+use reqwest::header::RETRY_AFTER;
 use reqwest::Client;
+use serde::Deserialize;
+use tokio::time::sleep;
+
+/// The `error` object of a Frost error response.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FrostApiError {
+    pub code: u16,
+    pub message: String,
+    #[serde(default)]
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct FrostErrorResponse {
+    error: FrostApiError,
+}
+
+impl fmt::Display for FrostApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.reason.is_empty() {
+            write!(f, "{} ({})", self.message, self.code)
+        } else {
+            write!(f, "{}: {} ({})", self.message, self.reason, self.code)
+        }
+    }
+}
 
 #[derive(Debug)]
 pub enum FrostError {
     RequestError(reqwest::Error),
-    ApiError(String),
+    Unauthorized(FrostApiError),
+    InvalidParameter(FrostApiError),
+    RateLimited {
+        error: FrostApiError,
+        retry_after: Option<Duration>,
+    },
+    NotFound(FrostApiError),
+    ServerError(FrostApiError),
+    ApiError(FrostApiError),
+    CircuitOpen,
 }
 
 impl fmt::Display for FrostError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result  {
         match self {
             FrostError::RequestError(e) => write!(f, "HTTP request failed: {}", e),
-            FrostError::ApiError(msg) => write!(f, "API error response: {}", msg)
+            FrostError::Unauthorized(e) => write!(f, "unauthorized: {}", e),
+            FrostError::InvalidParameter(e) => write!(f, "invalid parameter: {}", e),
+            FrostError::RateLimited { error, .. } => write!(f, "rate limited: {}", error),
+            FrostError::NotFound(e) => write!(f, "no data found: {}", e),
+            FrostError::ServerError(e) => write!(f, "server error: {}", e),
+            FrostError::ApiError(e) => write!(f, "API error response: {}", e),
+            FrostError::CircuitOpen => write!(f, "circuit open, Frost requests are paused"),
         }
     }
 }
@@ -24,7 +74,54 @@ impl error::Error for FrostError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         match self {
             FrostError::RequestError(e) => Some(e),
-            FrostError::ApiError(_) => None,
+            _ => None,
+        }
+    }
+}
+
+impl FrostError {
+    /// Builds a typed error from the HTTP status and body of a failed response.
+    /// Frost reports "no data" as 404 or 412.
+    pub fn from_response(status: u16, body: &str, retry_after: Option<Duration>) -> FrostError {
+        let error = match serde_json::from_str::<FrostErrorResponse>(body) {
+            Ok(response) => response.error,
+            Err(_) => FrostApiError {
+                code: status,
+                message: body.trim().to_string(),
+                reason: String::new(),
+            },
+        };
+
+        // A successful status with an error body is classified by the body's code
+        let code = if (200..300).contains(&status) {
+            error.code
+        } else {
+            status
+        };
+        match code {
+            401 | 403 => FrostError::Unauthorized(error),
+            400 => FrostError::InvalidParameter(error),
+            404 | 412 => FrostError::NotFound(error),
+            429 => FrostError::RateLimited { error, retry_after },
+            500..=599 => FrostError::ServerError(error),
+            _ => FrostError::ApiError(error),
+        }
+    }
+
+    /// Whether the request may succeed if sent again, e.g. timeouts, rate
+    /// limiting and server errors. Bad credentials or parameters are fatal.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            FrostError::RequestError(e) => e.is_timeout() || e.is_connect() || e.is_request(),
+            FrostError::RateLimited { .. } | FrostError::ServerError(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            FrostError::RateLimited { retry_after, .. } => *retry_after,
+            _ => None,
         }
     }
 }
@@ -36,44 +133,632 @@ impl From<reqwest::Error> for FrostError {
 }
 
 
-async fn get_observations(
-    frost_client: &str, 
-    frost_secret: &str, 
-    max_age: &str
-) -> Result<Vec<UalfData>, FrostError> {
-    let client = Client::new();
+const DEFAULT_BASE_URL: &str = "https://frost.met.no";
+const LIGHTNING_PATH: &str = "/lightning/v0.ualf";
+
+pub struct FrostConfig {
+    pub client_id: String,
+    pub client_secret: String,
+    pub base_url: String,           // Scheme and host, e.g. a local mock server or proxy
+    pub timeout: Duration,          // Total time allowed for a request
+    pub connect_timeout: Duration,  // Time allowed to establish a connection
+    pub max_interval: Duration,     // Longest interval fetched in a single request
+    pub region: Option<Region>,     // Only observations inside this region are returned
+    pub max_retries: u32,           // Retries of a failed request before giving up
+    pub base_backoff: Duration,     // Delay before the first retry, doubled for each retry
+    pub max_backoff: Duration,      // Upper bound on the delay between retries
+    pub failure_threshold: u32,     // Consecutive failed requests before the circuit opens
+    pub circuit_cooldown: Duration, // Time the circuit stays open before trying again
+}
+
+impl FrostConfig {
+    pub fn new(client_id: &str, client_secret: &str) -> FrostConfig {
+        FrostConfig {
+            client_id: client_id.to_string(),
+            client_secret: client_secret.to_string(),
+            base_url: DEFAULT_BASE_URL.to_string(),
+            timeout: Duration::from_secs(30),
+            connect_timeout: Duration::from_secs(10),
+            max_interval: Duration::from_secs(60 * 60),
+            region: None,
+            max_retries: 3,
+            base_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            failure_threshold: 5,
+            circuit_cooldown: Duration::from_secs(120),
+        }
+    }
+}
+
+/// Formats a window as an ISO 8601 duration for the `maxage` parameter.
+fn max_age(window: Duration) -> String {
+    let seconds = window.as_secs();
+    if seconds.is_multiple_of(60) {
+        format!("PT{}M", seconds / 60)
+    } else {
+        format!("PT{}S", seconds)
+    }
+}
+
+/// Parses an ISO 8601 interval such as `2024-07-01T00:00Z/2024-07-02T00:00Z`.
+pub fn parse_interval(interval: &str) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    fn parse_time(time: &str) -> Option<DateTime<Utc>> {
+        if let Ok(datetime) = DateTime::parse_from_rfc3339(time) {
+            return Some(datetime.with_timezone(&Utc));
+        }
+        NaiveDateTime::parse_from_str(time, "%Y-%m-%dT%H:%MZ")
+            .ok()
+            .map(|datetime| datetime.and_utc())
+    }
 
-    let response = client
-        .get("https://frost.met.no/lightning/v0.ualf")
-        .query(&[("referencetime", "latest"), ("maxage", max_age)])
-        .basic_auth(frost_client, Some(frost_secret))
-        .send()
-        .await?;
-    let ualf_text_data = response.text().await?;
+    let (start, end) = interval.split_once('/')?;
+    let start = parse_time(start)?;
+    let end = parse_time(end)?;
+    if start >= end {
+        return None;
+    }
+    Some((start, end))
+}
+
+/// Parses a `Retry-After` header given either in seconds or as an HTTP date.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    if let Ok(seconds) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let retry_at = DateTime::parse_from_rfc2822(value.trim()).ok()?;
+    (retry_at.with_timezone(&Utc) - Utc::now()).to_std().ok()
+}
+
+/// Client for the Frost lightning API. The underlying HTTP client keeps its
+/// connections pooled, so a single `FrostClient` should be shared between polls.
+pub struct FrostClient {
+    client: Client,
+    config: FrostConfig,
+    last_error: Mutex<Option<String>>,
+    circuit_breaker: Mutex<CircuitBreaker>,
+}
+
+impl FrostClient {
+    pub fn new(config: FrostConfig) -> Result<FrostClient, FrostError> {
+        let client = Client::builder()
+            .timeout(config.timeout)
+            .connect_timeout(config.connect_timeout)
+            .build()?;
+
+        let circuit_breaker =
+            CircuitBreaker::new("FROST", config.failure_threshold, config.circuit_cooldown);
+
+        Ok(FrostClient {
+            client,
+            config,
+            last_error: Mutex::new(None),
+            circuit_breaker: Mutex::new(circuit_breaker),
+        })
+    }
+
+    pub fn circuit_state(&self) -> CircuitState {
+        self.circuit_breaker.lock().unwrap().state()
+    }
+
+    /// Exponential backoff with full jitter for the given retry attempt.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .config
+            .base_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.config.max_backoff);
+        exponential.mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+    }
+
+    /// Sends a request, retrying retryable errors with backoff. Requests are
+    /// refused while the circuit breaker is open, and a rate limit that asks
+    /// for a longer wait than `max_backoff` is returned rather than slept on.
+    async fn get_observations(&self, query: &[(&str, &str)]) -> Result<Vec<UalfData>, FrostError> {
+        if !self.circuit_breaker.lock().unwrap().allow_request() {
+            return Err(FrostError::CircuitOpen);
+        }
+
+        let mut attempt = 0;
+        loop {
+            let error = match self.send_request(query).await {
+                Ok(observations) => {
+                    self.circuit_breaker.lock().unwrap().record_success();
+                    return Ok(observations);
+                }
+                // Frost answered, there just was nothing to return
+                Err(e @ FrostError::NotFound(_)) => {
+                    self.circuit_breaker.lock().unwrap().record_success();
+                    return Err(e);
+                }
+                Err(e) => e,
+            };
+
+            // A Retry-After beyond max_backoff is left for the caller to wait out
+            let delay = error
+                .retry_after()
+                .unwrap_or_else(|| self.backoff(attempt));
+            if !error.is_retryable()
+                || attempt >= self.config.max_retries
+                || delay > self.config.max_backoff
+            {
+                self.circuit_breaker.lock().unwrap().record_failure();
+                return Err(error);
+            }
+
+            attempt += 1;
+            warn!(
+                "Frost request failed ({}), retry {}/{} in {:?}",
+                error, attempt, self.config.max_retries, delay
+            );
+            sleep(delay).await;
+        }
+    }
+
+    async fn send_request(&self, query: &[(&str, &str)]) -> Result<Vec<UalfData>, FrostError> {
+        let url = format!(
+            "{}{}",
+            self.config.base_url.trim_end_matches('/'),
+            LIGHTNING_PATH
+        );
+        let mut request = self.client.get(url).query(query);
+        if let Some(region) = &self.config.region {
+            request = request.query(&[("geometry", region.to_wkt())]);
+        }
+        let response = request
+            .basic_auth(&self.config.client_id, Some(&self.config.client_secret))
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let retry_after = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(parse_retry_after);
+            let body = response.text().await.unwrap_or_default();
+            return Err(FrostError::from_response(status.as_u16(), &body, retry_after));
+        }
+
+        let ualf_text_data = response.text().await?;
+
+        if ualf_text_data.starts_with('{') {
+            return Err(FrostError::from_response(status.as_u16(), &ualf_text_data, None));
+        }
+
+        let mut observations = read_ualf(ualf_text_data.as_bytes()).unwrap_or_default();
+
+        // The API matches geometry loosely, so make sure nothing outside the region gets through
+        if let Some(region) = &self.config.region {
+            observations.retain(|obs| region.contains_observation(obs));
+        }
+
+        Ok(observations)
+    }
+
+    pub async fn get_latest_observations(
+        &self,
+        window: Duration,
+    ) -> Result<Vec<UalfData>, FrostError> {
+        self.get_observations(&[("referencetime", "latest"), ("maxage", &max_age(window))])
+            .await
+    }
+
+    /// Fetches all observations in `[start, end)`, split into requests of at
+    /// most `max_interval` each.
+    pub async fn get_observations_between(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<UalfData>, FrostError> {
+        let max_interval = TimeDelta::from_std(self.config.max_interval)
+            .unwrap_or(TimeDelta::hours(1))
+            .max(TimeDelta::seconds(1));
+
+        let mut observations: Vec<UalfData> = vec![];
+        let mut chunk_start = start;
+        while chunk_start < end {
+            let chunk_end = (chunk_start + max_interval).min(end);
+            let reference_time = format!(
+                "{}/{}",
+                chunk_start.format("%Y-%m-%dT%H:%M:%SZ"),
+                chunk_end.format("%Y-%m-%dT%H:%M:%SZ")
+            );
+            debug!("Fetching Frost observations for {}", reference_time);
+
+            // Drop strikes on the chunk boundary so they are only counted once
+            let start_ns = chunk_start.timestamp_nanos_opt().unwrap_or(i64::MIN);
+            let end_ns = chunk_end.timestamp_nanos_opt().unwrap_or(i64::MAX);
+            let chunk_observations =
+                match self.get_observations(&[("referencetime", &reference_time)]).await {
+                    Ok(observations) => observations,
+                    Err(FrostError::NotFound(_)) => vec![],
+                    Err(e) => return Err(e),
+                };
+            observations.extend(
+                chunk_observations
+                    .into_iter()
+                    .filter(|obs| obs.epoch_ns >= start_ns && obs.epoch_ns < end_ns),
+            );
+            chunk_start = chunk_end;
+        }
 
-    if ualf_text_data.starts_with("{") {
-        return Err(FrostError::ApiError(ualf_text_data));
+        Ok(observations)
     }
 
-    let observations: Vec<UalfData> = ualf_text_data
-        .split("\n")
-        .filter(|obs| !obs.is_empty())
-        .filter_map(UalfData::from_string)
-        .collect();
+    pub async fn get_latest_10m_observations(&self) -> Result<Vec<UalfData>, FrostError> {
+        self.get_latest_observations(Duration::from_secs(10 * 60)).await
+    }
 
-    Ok(observations)
+    pub async fn get_latest_1h_observations(&self) -> Result<Vec<UalfData>, FrostError> {
+        self.get_latest_observations(Duration::from_secs(60 * 60)).await
+    }
 }
 
-pub async fn get_latest_10m_observations(
-    frost_client: &str, 
-    frost_secret: &str
-) -> Result<Vec<UalfData>, FrostError> {
-    get_observations(frost_client, frost_secret, "PT10M").await
+impl LightningSource for FrostClient {
+    fn name(&self) -> &str {
+        "frost"
+    }
+
+    async fn fetch(&self, window: Duration) -> Result<Vec<UalfData>, SourceError> {
+        let result = self.get_latest_observations(window).await;
+        *self.last_error.lock().unwrap() = result.as_ref().err().map(|e| e.to_string());
+        Ok(result?)
+    }
+
+    fn health(&self) -> SourceHealth {
+        let last_error = self.last_error.lock().unwrap().clone();
+        let circuit_breaker = self.circuit_breaker.lock().unwrap();
+        match (circuit_breaker.state(), last_error) {
+            (CircuitState::Open, _) => SourceHealth::Down(format!(
+                "circuit open for {:?}",
+                circuit_breaker
+                    .opened_at()
+                    .map(|opened_at| opened_at.elapsed())
+                    .unwrap_or_default()
+            )),
+            (_, Some(msg)) => SourceHealth::Degraded(msg),
+            (_, None) => SourceHealth::Healthy,
+        }
+    }
 }
 
-pub async fn get_latest_1h_observations(
-    frost_client: &str, 
-    frost_secret: &str
-) -> Result<Vec<UalfData>, FrostError> {
-    get_observations(frost_client, frost_secret, "PT1H").await
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    const LINE: &str = concat!(
+        "0 2024 7 1 12 30 15 123456789 59.9123 10.7456 -12 1 6 4 ",
+        "45.5 0.6 0.3 1.2 5.3 12.1 3.2 0 1 0 1"
+    );
+
+    /// A local stand-in for Frost. Every request head is recorded and answered
+    /// with whatever `respond` returns for it, on a fresh connection.
+    struct MockFrost {
+        base_url: String,
+        requests: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl MockFrost {
+        async fn start<F>(respond: F) -> MockFrost
+        where
+            F: Fn(&str) -> String + Send + Sync + 'static,
+        {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let base_url = format!("http://{}", listener.local_addr().unwrap());
+            let requests = Arc::new(Mutex::new(vec![]));
+            let recorded = requests.clone();
+            tokio::spawn(async move {
+                loop {
+                    let Ok((mut stream, _)) = listener.accept().await else {
+                        return;
+                    };
+                    let mut head = vec![];
+                    let mut buffer = [0u8; 1024];
+                    while !head.ends_with(b"\r\n\r\n") {
+                        match stream.read(&mut buffer).await {
+                            Ok(0) | Err(_) => break,
+                            Ok(read) => head.extend_from_slice(&buffer[..read]),
+                        }
+                    }
+                    let head = String::from_utf8_lossy(&head).to_string();
+                    let response = respond(&head);
+                    recorded.lock().unwrap().push(head);
+                    let _ = stream.write_all(response.as_bytes()).await;
+                }
+            });
+            MockFrost { base_url, requests }
+        }
+
+        fn requests(&self) -> Vec<String> {
+            self.requests.lock().unwrap().clone()
+        }
+
+        fn client(&self, base_url: &str) -> FrostClient {
+            let mut config = FrostConfig::new("client", "secret");
+            config.base_url = base_url.to_string();
+            config.base_backoff = Duration::from_millis(1);
+            config.max_backoff = Duration::from_millis(10);
+            FrostClient::new(config).unwrap()
+        }
+    }
+
+    fn response(status: &str, headers: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n{}\r\n{}",
+            status,
+            body.len(),
+            headers,
+            body
+        )
+    }
+
+    /// The first line of a request head, e.g. `GET /path?query HTTP/1.1`.
+    fn request_line(head: &str) -> &str {
+        head.lines().next().unwrap_or_default()
+    }
+
+    fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+        head.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.eq_ignore_ascii_case(name).then(|| value.trim())
+        })
+    }
+
+    #[tokio::test]
+    async fn requests_lightning_from_the_base_url_with_basic_auth() {
+        let body = format!("{}\n{}\n", LINE, LINE.replace("59.9123", "60.5"));
+        let frost = MockFrost::start(move |_| response("200 OK", "", &body)).await;
+        let expected = vec![
+            UalfData::from_string(LINE).unwrap(),
+            UalfData::from_string(&LINE.replace("59.9123", "60.5")).unwrap(),
+        ];
+
+        for base_url in [frost.base_url.clone(), format!("{}/", frost.base_url)] {
+            let client = frost.client(&base_url);
+            assert_eq!(client.get_latest_10m_observations().await.unwrap(), expected);
+        }
+
+        let requests = frost.requests();
+        assert_eq!(requests.len(), 2);
+        for request in &requests {
+            assert_eq!(
+                request_line(request),
+                "GET /lightning/v0.ualf?referencetime=latest&maxage=PT10M HTTP/1.1"
+            );
+            // base64 of "client:secret"
+            assert_eq!(header(request, "authorization"), Some("Basic Y2xpZW50OnNlY3JldA=="));
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_a_short_retry_after_and_returns_a_long_one() {
+        let short = MockFrost::start(|_| {
+            response("429 Too Many Requests", "Retry-After: 0\r\n", "slow down")
+        })
+        .await;
+        assert!(matches!(
+            short.client(&short.base_url).get_latest_10m_observations().await,
+            Err(FrostError::RateLimited { .. })
+        ));
+        // The first request and max_retries retries
+        assert_eq!(short.requests().len(), 4);
+
+        let long = MockFrost::start(|_| {
+            response("429 Too Many Requests", "Retry-After: 3600\r\n", "slow down")
+        })
+        .await;
+        let started = std::time::Instant::now();
+        let client = long.client(&long.base_url);
+        let error = client.get_latest_10m_observations().await.unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(3600)));
+        assert_eq!(long.requests().len(), 1);
+    }
+
+    #[test]
+    fn backoff_stays_within_the_doubled_delay_and_the_cap() {
+        let mut config = FrostConfig::new("client", "secret");
+        config.base_backoff = Duration::from_millis(100);
+        config.max_backoff = Duration::from_millis(500);
+        let client = FrostClient::new(config).unwrap();
+
+        for (attempt, bound_ms) in [(0, 100), (1, 200), (2, 400), (3, 500), (10, 500), (40, 500)] {
+            for _ in 0..100 {
+                assert!(client.backoff(attempt) <= Duration::from_millis(bound_ms));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn only_transient_errors_are_retryable() {
+        for status in [429, 500, 502, 503] {
+            assert!(FrostError::from_response(status, "", None).is_retryable(), "{}", status);
+        }
+        for status in [400, 401, 403, 404, 412] {
+            assert!(!FrostError::from_response(status, "", None).is_retryable(), "{}", status);
+        }
+        assert!(!FrostError::CircuitOpen.is_retryable());
+
+        // Nothing listens on the discard port, so the connection is refused
+        let mut config = FrostConfig::new("client", "secret");
+        config.base_url = "http://127.0.0.1:9".to_string();
+        config.max_retries = 0;
+        let error = FrostClient::new(config)
+            .unwrap()
+            .get_latest_10m_observations()
+            .await
+            .unwrap_err();
+        assert!(matches!(error, FrostError::RequestError(_)));
+        assert!(error.is_retryable());
+    }
+
+    #[test]
+    fn parses_retry_after_in_seconds_and_as_a_date() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after(" 5 "), Some(Duration::from_secs(5)));
+
+        let in_a_minute = Utc::now() + TimeDelta::seconds(60);
+        let header = in_a_minute.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+        let delay = parse_retry_after(&header).unwrap();
+        assert!(delay > Duration::from_secs(55) && delay <= Duration::from_secs(60));
+
+        // A date already past leaves nothing to wait for
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    /// An error response as Frost sends it, around the given `error` object.
+    fn error_body(code: u16, message: &str, reason: &str) -> String {
+        format!(
+            concat!(
+                r#"{{"@context":"https://frost.met.no/schema","@type":"ErrorResponse","#,
+                r#""apiVersion":"v0","license":"https://creativecommons.org/licenses/by/3.0/no/","#,
+                r#""createdAt":"2024-07-01T12:00:00Z","queryTime":0.01,"currentItemCount":0,"#,
+                r#""itemsPerPage":0,"offset":0,"totalItemCount":0,"#,
+                r#""currentLink":"https://frost.met.no/lightning/v0.ualf","#,
+                r#""error":{{"code":{},"message":"{}","reason":"{}"}}}}"#
+            ),
+            code, message, reason
+        )
+    }
+
+    #[test]
+    fn classifies_frost_error_responses() {
+        let unauthorized = error_body(401, "Unauthorized", "Invalid credentials");
+        match FrostError::from_response(401, &unauthorized, None) {
+            FrostError::Unauthorized(error) => {
+                assert_eq!(error.code, 401);
+                assert_eq!(error.message, "Unauthorized");
+                assert_eq!(error.reason, "Invalid credentials");
+            }
+            other => panic!("{:?}", other),
+        }
+
+        let bad_request = error_body(400, "Invalid parameter value", "Unknown geometry");
+        assert!(matches!(
+            FrostError::from_response(400, &bad_request, None),
+            FrostError::InvalidParameter(error) if error.reason == "Unknown geometry"
+        ));
+        for status in [404, 412] {
+            let no_data = error_body(status, "No data found", "No lightning in the interval");
+            assert!(matches!(
+                FrostError::from_response(status, &no_data, None),
+                FrostError::NotFound(error) if error.code == status
+            ));
+        }
+
+        let too_many = error_body(429, "Too Many Requests", "Rate limit exceeded");
+        let retry_after = Some(Duration::from_secs(30));
+        let rate_limited = FrostError::from_response(429, &too_many, retry_after);
+        assert!(matches!(rate_limited, FrostError::RateLimited { .. }));
+        assert_eq!(rate_limited.retry_after(), retry_after);
+
+        for status in [500, 503] {
+            let server = error_body(status, "Internal Server Error", "");
+            let error = FrostError::from_response(status, &server, None);
+            assert!(matches!(error, FrostError::ServerError(_)));
+            let message = format!("server error: Internal Server Error ({})", status);
+            assert_eq!(error.to_string(), message);
+        }
+    }
+
+    #[test]
+    fn keeps_a_body_that_is_not_json_as_the_message() {
+        let error = FrostError::from_response(502, "<html>Bad Gateway</html>\n", None);
+        match error {
+            FrostError::ServerError(error) => {
+                assert_eq!(error.code, 502);
+                assert_eq!(error.message, "<html>Bad Gateway</html>");
+                assert_eq!(error.reason, "");
+            }
+            other => panic!("{:?}", other),
+        }
+        assert!(matches!(
+            FrostError::from_response(418, "teapot", None),
+            FrostError::ApiError(error) if error.code == 418
+        ));
+    }
+
+    #[test]
+    fn classifies_an_error_body_sent_with_a_success_status_by_its_code() {
+        let no_data = error_body(412, "No data found", "");
+        assert!(matches!(
+            FrostError::from_response(200, &no_data, None),
+            FrostError::NotFound(error) if error.code == 412
+        ));
+        let unauthorized = error_body(401, "Unauthorized", "");
+        assert!(matches!(
+            FrostError::from_response(200, &unauthorized, None),
+            FrostError::Unauthorized(_)
+        ));
+    }
+
+    fn utc(rfc3339: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(rfc3339).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn parses_intervals_in_both_forms() {
+        let expected = Some((utc("2024-07-01T00:00:00Z"), utc("2024-07-02T00:00:00Z")));
+        assert_eq!(parse_interval("2024-07-01T00:00Z/2024-07-02T00:00Z"), expected);
+        assert_eq!(
+            parse_interval("2024-07-01T00:00:00Z/2024-07-02T02:00:00+02:00"),
+            expected
+        );
+        assert_eq!(
+            parse_interval("2024-07-01T00:00Z/2024-07-01T00:30:15.5Z"),
+            Some((utc("2024-07-01T00:00:00Z"), utc("2024-07-01T00:30:15.5Z")))
+        );
+    }
+
+    #[test]
+    fn rejects_empty_reversed_and_malformed_intervals() {
+        assert_eq!(parse_interval("2024-07-01T00:00Z/2024-07-01T00:00Z"), None);
+        assert_eq!(parse_interval("2024-07-02T00:00Z/2024-07-01T00:00Z"), None);
+        assert_eq!(parse_interval("2024-07-01T00:00Z"), None);
+        assert_eq!(parse_interval("2024-07-01/2024-07-02"), None);
+    }
+
+    #[tokio::test]
+    async fn fetches_a_range_hour_by_hour_without_duplicating_boundary_strikes() {
+        // Every request gets the same strikes, including ones on chunk boundaries
+        let times = ["0 0", "1 0", "1 30", "2 0", "3 0"];
+        let body: String = times
+            .iter()
+            .map(|time| LINE.replace("12 30 15 123456789", &format!("{} 0 0", time)) + "\n")
+            .collect();
+        let frost = MockFrost::start(move |_| response("200 OK", "", &body)).await;
+        let client = frost.client(&frost.base_url);
+
+        let start = utc("2024-07-01T00:00:00Z");
+        let end = utc("2024-07-01T03:00:00Z");
+        let observations = client.get_observations_between(start, end).await.unwrap();
+        let fetched: Vec<String> = observations
+            .iter()
+            .map(|observation| observation.timestamp().format("%H:%M").to_string())
+            .collect();
+        assert_eq!(fetched, vec!["00:00", "01:00", "01:30", "02:00"]);
+
+        let requests: Vec<String> = frost
+            .requests()
+            .iter()
+            .map(|request| request_line(request).to_string())
+            .collect();
+        let hour = |hour: u32| format!("2024-07-01T{:02}%3A00%3A00Z", hour);
+        let reference_times: Vec<String> = (0..3)
+            .map(|from| {
+                format!(
+                    "GET /lightning/v0.ualf?referencetime={}%2F{} HTTP/1.1",
+                    hour(from),
+                    hour(from + 1)
+                )
+            })
+            .collect();
+        assert_eq!(requests, reference_times);
+    }
+}
@@ -0,0 +1,260 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dbscan::{DbscanCluster, StormMetrics},
+    nowcast::Nowcast,
+    storm_tracker::TrackedStorm,
+};
+
+/// A GeoJSON position, `[longitude, latitude]` as RFC 7946 requires.
+pub type Position = [f64; 2];
+
+/// The RFC 7946 geometries a storm outline can take. Outlines of fewer than
+/// three distinct strikes are points or lines rather than polygons.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Geometry {
+    Point {
+        coordinates: Position,
+    },
+    LineString {
+        coordinates: Vec<Position>,
+    },
+    Polygon {
+        coordinates: Vec<Vec<Position>>,
+    },
+    MultiPolygon {
+        coordinates: Vec<Vec<Vec<Position>>>,
+    },
+}
+
+impl Geometry {
+    /// Builds a geometry from (latitude, longitude) outlines, a `Polygon` for
+    /// one outline and a `MultiPolygon` for several.
+    pub fn from_polygons(polygons: &[Vec<(f64, f64)>]) -> Geometry {
+        if let [polygon] = polygons {
+            let mut positions: Vec<Position> = polygon.iter().map(|p| [p.1, p.0]).collect();
+            positions.dedup();
+            if positions.len() > 1 && positions.first() == positions.last() {
+                positions.pop();
+            }
+            return match positions.len() {
+                0 => Geometry::MultiPolygon {
+                    coordinates: vec![],
+                },
+                1 => Geometry::Point {
+                    coordinates: positions[0],
+                },
+                2 => Geometry::LineString {
+                    coordinates: positions,
+                },
+                _ => Geometry::Polygon {
+                    coordinates: vec![exterior_ring(positions)],
+                },
+            };
+        }
+
+        Geometry::MultiPolygon {
+            coordinates: polygons
+                .iter()
+                .map(|polygon| {
+                    let positions: Vec<Position> = polygon.iter().map(|p| [p.1, p.0]).collect();
+                    vec![exterior_ring(positions)]
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Closes the ring and winds it counterclockwise, as RFC 7946 requires of
+/// exterior rings.
+fn exterior_ring(mut positions: Vec<Position>) -> Vec<Position> {
+    if positions.first() == positions.last() {
+        positions.pop();
+    }
+    let twice_area: f64 = positions
+        .iter()
+        .zip(positions.iter().cycle().skip(1))
+        .map(|(a, b)| a[0] * b[1] - b[0] * a[1])
+        .sum();
+    if twice_area < 0.0 {
+        positions.reverse();
+    }
+    if let Some(first) = positions.first().copied() {
+        positions.push(first);
+    }
+    positions
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StormProperties {
+    pub storm_id: i64,
+    pub lead_time_minutes: i64, // 0 for the observed outline, otherwise a nowcast
+    pub strike_count: usize,
+    pub mean_current_ka: f64,
+    pub time_span_s: f64,
+    #[serde(flatten)]
+    pub metrics: StormMetrics,
+}
+
+impl StormProperties {
+    pub fn new(storm_id: i64, cluster: &DbscanCluster, metrics: StormMetrics) -> StormProperties {
+        StormProperties {
+            storm_id,
+            lead_time_minutes: 0,
+            strike_count: cluster.points.len(),
+            mean_current_ka: cluster.average_current(),
+            time_span_s: cluster.time_span_ns() as f64 / 1e9,
+            metrics,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct Feature {
+    pub geometry: Geometry,
+    pub properties: StormProperties,
+}
+
+impl Feature {
+    /// The storm's observed outline.
+    pub fn storm(
+        storm: &TrackedStorm,
+        polygons: &[Vec<(f64, f64)>],
+        metrics: StormMetrics,
+    ) -> Feature {
+        Feature {
+            geometry: Geometry::from_polygons(polygons),
+            properties: StormProperties::new(storm.storm_id, &storm.cluster, metrics),
+        }
+    }
+
+    /// Where the storm is expected to be at the nowcast's lead time.
+    pub fn nowcast(storm: &TrackedStorm, nowcast: &Nowcast, metrics: StormMetrics) -> Feature {
+        let mut properties = StormProperties::new(storm.storm_id, &storm.cluster, metrics);
+        properties.lead_time_minutes = (nowcast.lead_time.as_secs() / 60) as i64;
+        Feature {
+            geometry: Geometry::from_polygons(&nowcast.polygons),
+            properties,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct FeatureCollection {
+    pub features: Vec<Feature>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    use crate::ualf::strike_near;
+
+    // A degree square at 60-61°N, 10-11°E, as (latitude, longitude)
+    const CLOCKWISE: [(f64, f64); 4] = [(60.0, 10.0), (61.0, 10.0), (61.0, 11.0), (60.0, 11.0)];
+    const COUNTERCLOCKWISE: [(f64, f64); 4] =
+        [(60.0, 10.0), (60.0, 11.0), (61.0, 11.0), (61.0, 10.0)];
+
+    fn to_json(geometry: Geometry) -> serde_json::Value {
+        serde_json::to_value(geometry).unwrap()
+    }
+
+    #[test]
+    fn writes_closed_counterclockwise_rings_in_longitude_latitude_order() {
+        let expected = json!({
+            "type": "Polygon",
+            "coordinates": [[[10.0, 60.0], [11.0, 60.0], [11.0, 61.0], [10.0, 61.0], [10.0, 60.0]]]
+        });
+        assert_eq!(
+            to_json(Geometry::from_polygons(&[COUNTERCLOCKWISE.to_vec()])),
+            expected
+        );
+        // A closed ring is not closed twice
+        let mut closed = COUNTERCLOCKWISE.to_vec();
+        closed.push(closed[0]);
+        assert_eq!(to_json(Geometry::from_polygons(&[closed])), expected);
+
+        // A clockwise ring is turned around
+        assert_eq!(
+            to_json(Geometry::from_polygons(&[CLOCKWISE.to_vec()])),
+            json!({
+                "type": "Polygon",
+                "coordinates": [
+                    [[11.0, 60.0], [11.0, 61.0], [10.0, 61.0], [10.0, 60.0], [11.0, 60.0]]
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn writes_several_parts_as_a_multipolygon() {
+        let north: Vec<(f64, f64)> = CLOCKWISE
+            .iter()
+            .map(|(lat, lon)| (lat + 2.0, *lon))
+            .collect();
+        let geometry = Geometry::from_polygons(&[COUNTERCLOCKWISE.to_vec(), north]);
+        assert_eq!(
+            to_json(geometry),
+            json!({
+                "type": "MultiPolygon",
+                "coordinates": [
+                    [[[10.0, 60.0], [11.0, 60.0], [11.0, 61.0], [10.0, 61.0], [10.0, 60.0]]],
+                    [[[11.0, 62.0], [11.0, 63.0], [10.0, 63.0], [10.0, 62.0], [11.0, 62.0]]]
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn writes_one_or_two_distinct_strikes_as_a_point_or_a_line() {
+        assert_eq!(
+            to_json(Geometry::from_polygons(&[vec![(60.0, 10.0), (60.0, 10.0)]])),
+            json!({"type": "Point", "coordinates": [10.0, 60.0]})
+        );
+        assert_eq!(
+            to_json(Geometry::from_polygons(&[vec![
+                (60.0, 10.0),
+                (60.5, 10.0),
+                (60.0, 10.0)
+            ]])),
+            json!({"type": "LineString", "coordinates": [[10.0, 60.0], [10.0, 60.5]]})
+        );
+    }
+
+    #[test]
+    fn tags_features_and_collections_with_their_type() {
+        let cluster = DbscanCluster {
+            points: vec![
+                strike_near(0, 0.0, 0.0),
+                strike_near(60_000_000_000, 0.0, 0.0),
+            ],
+            cluster_id: 0,
+        };
+        let metrics = cluster.metrics(&[vec![(60.0, 10.0)]]);
+        let storm = TrackedStorm {
+            storm_id: 42,
+            cluster,
+            motion: None,
+        };
+        let feature = Feature::storm(&storm, &[vec![(60.0, 10.0)]], metrics);
+        let collection = serde_json::to_value(FeatureCollection {
+            features: vec![feature],
+        })
+        .unwrap();
+
+        assert_eq!(collection["type"], "FeatureCollection");
+        let feature = &collection["features"][0];
+        assert_eq!(feature["type"], "Feature");
+        assert_eq!(feature["geometry"]["type"], "Point");
+        assert_eq!(feature["properties"]["storm_id"], 42);
+        assert_eq!(feature["properties"]["strike_count"], 2);
+        assert_eq!(feature["properties"]["time_span_s"], 60.0);
+        // Metrics sit alongside the other properties
+        assert_eq!(feature["properties"]["max_current_ka"], -12);
+    }
+}
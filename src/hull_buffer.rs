@@ -0,0 +1,198 @@
+use crate::{
+    concave_hull::compute_concave_hull_of_points, convex_hull::compute_convex_hull_of_points,
+    projection::destination,
+};
+
+// Vertices approximating a full circle, so each rounded corner gets its share
+const CIRCLE_SEGMENTS: usize = 32;
+
+/// Points on a geodesic circle of `distance_km` around every vertex.
+fn vertex_circles(polygons: &[Vec<(f64, f64)>], distance_km: f64) -> Vec<(f64, f64)> {
+    let mut points: Vec<(f64, f64)> = vec![];
+    for vertex in polygons.iter().flatten() {
+        for segment in 0..CIRCLE_SEGMENTS {
+            let bearing_deg = 360.0 * segment as f64 / CIRCLE_SEGMENTS as f64;
+            points.push(destination(*vertex, bearing_deg, distance_km));
+        }
+    }
+    points
+}
+
+/// Grows a convex polygon by `distance_km` in every direction, with rounded
+/// corners. The hull of circles around each vertex of a convex polygon is
+/// exactly its buffer, up to the circles being polygons themselves.
+pub fn buffer_convex_polygon(polygon: &[(f64, f64)], distance_km: f64) -> Vec<(f64, f64)> {
+    if distance_km <= 0.0 || polygon.is_empty() {
+        return polygon.to_vec();
+    }
+    compute_convex_hull_of_points(vertex_circles(&[polygon.to_vec()], distance_km))
+}
+
+/// Grows concave polygons by `distance_km`, merging parts whose buffers
+/// overlap. The outline is the alpha shape of the circles around each vertex,
+/// with an alpha radius widened by the buffer so the circles join up.
+pub fn buffer_concave_polygons(
+    polygons: &[Vec<(f64, f64)>],
+    distance_km: f64,
+    alpha_km: f64,
+) -> Vec<Vec<(f64, f64)>> {
+    if distance_km <= 0.0 || polygons.is_empty() {
+        return polygons.to_vec();
+    }
+    compute_concave_hull_of_points(
+        vertex_circles(polygons, distance_km),
+        alpha_km.hypot(distance_km),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        projection::{polygon_area_km2, AzimuthalEquidistant},
+        ualf::{strike_near, TEST_ORIGIN},
+    };
+
+    // How far inside a circle its 32-segment approximation may cut
+    const CHORD_FACTOR: f64 = 0.995;
+
+    fn at_km(north_km: f64, east_km: f64) -> (f64, f64) {
+        let strike = strike_near(0, north_km, east_km);
+        (strike.latitude, strike.longitude)
+    }
+
+    fn to_km(point: (f64, f64)) -> (f64, f64) {
+        AzimuthalEquidistant::new(TEST_ORIGIN).project(point)
+    }
+
+    fn square_km(side_km: f64) -> Vec<(f64, f64)> {
+        [
+            (0.0, 0.0),
+            (0.0, side_km),
+            (side_km, side_km),
+            (side_km, 0.0),
+        ]
+        .map(|(north, east)| at_km(north, east))
+        .to_vec()
+    }
+
+    fn edges(polygon: &[(f64, f64)]) -> impl Iterator<Item = ((f64, f64), (f64, f64))> + '_ {
+        polygon
+            .iter()
+            .map(|p| to_km(*p))
+            .zip(polygon.iter().cycle().skip(1).map(|p| to_km(*p)))
+    }
+
+    fn distance_to_boundary_km(point: (f64, f64), polygon: &[(f64, f64)]) -> f64 {
+        let (n, e) = to_km(point);
+        edges(polygon)
+            .map(|(a, b)| {
+                let (dn, de) = (b.0 - a.0, b.1 - a.1);
+                let t = (((n - a.0) * dn + (e - a.1) * de) / (dn * dn + de * de)).clamp(0.0, 1.0);
+                (n - a.0 - t * dn).hypot(e - a.1 - t * de)
+            })
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    fn is_inside(point: (f64, f64), polygon: &[(f64, f64)]) -> bool {
+        let (n, e) = to_km(point);
+        edges(polygon)
+            .filter(|(a, b)| {
+                (a.0 > n) != (b.0 > n) && e < a.1 + (n - a.0) / (b.0 - a.0) * (b.1 - a.1)
+            })
+            .count()
+            % 2
+            == 1
+    }
+
+    // Every vertex lies inside one of the parts, at least `distance_km` from its edge
+    fn assert_buffered(hull: &[Vec<(f64, f64)>], buffered: &[Vec<(f64, f64)>], distance_km: f64) {
+        for vertex in hull.iter().flatten() {
+            let part = buffered.iter().find(|part| is_inside(*vertex, part));
+            let part = part.unwrap_or_else(|| panic!("{:?} is outside the buffer", vertex));
+            let clearance_km = distance_to_boundary_km(*vertex, part);
+            assert!(
+                clearance_km >= distance_km * CHORD_FACTOR,
+                "{}",
+                clearance_km
+            );
+        }
+    }
+
+    #[test]
+    fn a_convex_buffer_keeps_its_distance_with_rounded_corners() {
+        let square = square_km(10.0);
+        let buffered = buffer_convex_polygon(&square, 5.0);
+        assert_buffered(
+            std::slice::from_ref(&square),
+            std::slice::from_ref(&buffered),
+            5.0,
+        );
+
+        // No further out than the buffer either, up to the projection
+        for vertex in &buffered {
+            assert!(distance_to_boundary_km(*vertex, &square) <= 5.0 + 1e-3);
+        }
+
+        // Past the north-east corner the outline follows a circle around it
+        let rounded = buffered
+            .iter()
+            .map(|vertex| to_km(*vertex))
+            .filter(|(north, east)| *north > 10.0 + 1e-6 && *east > 10.0 + 1e-6)
+            .collect::<Vec<_>>();
+        assert!(rounded.len() >= 7, "{}", rounded.len());
+        for (north, east) in rounded {
+            assert!(((north - 10.0).hypot(east - 10.0) - 5.0).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn a_concave_buffer_keeps_its_distance_and_its_shape() {
+        let mut strikes: Vec<(f64, f64)> = vec![];
+        for north in (0..=20).step_by(2) {
+            for east in (0..=20).step_by(2) {
+                if north <= 4 || east <= 4 {
+                    strikes.push(at_km(north as f64, east as f64));
+                }
+            }
+        }
+        let hull = compute_concave_hull_of_points(strikes.clone(), 5.0);
+        let buffered = buffer_concave_polygons(&hull, 3.0, 5.0);
+        assert_eq!(buffered.len(), 1);
+        assert_buffered(&hull, &buffered, 3.0);
+
+        let convex = buffer_convex_polygon(&compute_convex_hull_of_points(strikes), 3.0);
+        // The notch of the L stays out of the buffer
+        assert!(polygon_area_km2(&buffered[0]) < 0.9 * polygon_area_km2(&convex));
+    }
+
+    #[test]
+    fn concave_parts_merge_once_their_buffers_overlap() {
+        let square = square_km(4.0);
+        let shifted = |east_km: f64| -> Vec<(f64, f64)> {
+            square
+                .iter()
+                .map(|vertex| {
+                    let (north, east) = to_km(*vertex);
+                    at_km(north, east + east_km)
+                })
+                .collect()
+        };
+
+        let apart = vec![square.clone(), shifted(30.0)];
+        assert_eq!(buffer_concave_polygons(&apart, 3.0, 5.0).len(), 2);
+        let close = vec![square.clone(), shifted(8.0)];
+        let merged = buffer_concave_polygons(&close, 3.0, 5.0);
+        assert_eq!(merged.len(), 1);
+        assert_buffered(&close, &merged, 3.0);
+    }
+
+    #[test]
+    fn no_buffer_returns_the_hull_unchanged() {
+        let square = square_km(10.0);
+        assert_eq!(buffer_convex_polygon(&square, 0.0), square);
+        let parts = vec![square.clone(), square_km(2.0)];
+        assert_eq!(buffer_concave_polygons(&parts, 0.0, 5.0), parts);
+    }
+}
@@ -1,7 +1,20 @@
+pub mod alert_state;
+pub mod circuit_breaker;
+pub mod concave_hull;
 pub mod db;
 pub mod frost;
+pub mod geojson;
+pub mod hull_buffer;
+pub mod lightning_source;
+pub mod location_forecast;
 pub mod location_utils;
+pub mod nowcast;
+pub mod projection;
+pub mod region;
 pub mod ualf;
 pub mod ualf_buffer;
+pub mod ualf_file;
 pub mod dbscan;
+pub mod storm_motion;
+pub mod storm_tracker;
 pub mod convex_hull;
\ No newline at end of file
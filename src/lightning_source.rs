@@ -0,0 +1,131 @@
+use std::error;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::frost::FrostError;
+use crate::ualf::UalfData;
+
+#[derive(Debug)]
+pub enum SourceError {
+    Frost(FrostError),
+    Io(io::Error),
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SourceError::Frost(e) => write!(f, "Frost API error: {}", e),
+            SourceError::Io(e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl error::Error for SourceError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            SourceError::Frost(e) => Some(e),
+            SourceError::Io(e) => Some(e),
+        }
+    }
+}
+
+impl From<FrostError> for SourceError {
+    fn from(err: FrostError) -> SourceError {
+        SourceError::Frost(err)
+    }
+}
+
+impl From<io::Error> for SourceError {
+    fn from(err: io::Error) -> SourceError {
+        SourceError::Io(err)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SourceHealth {
+    Healthy,
+    Degraded(String),
+    Down(String),
+}
+
+/// A provider of lightning observations, such as the Frost API or a UALF archive.
+pub trait LightningSource: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Returns the observations from the latest `window` of time.
+    fn fetch(
+        &self,
+        window: Duration,
+    ) -> impl Future<Output = Result<Vec<UalfData>, SourceError>> + Send;
+
+    fn health(&self) -> SourceHealth;
+
+    /// The source's current time, which for a replay lies in the past.
+    fn now_epoch_ns(&self) -> i64 {
+        Utc::now().timestamp_nanos_opt().unwrap_or(i64::MAX)
+    }
+
+    /// Whether every observation has been served, after which polling stops.
+    /// Live sources never finish.
+    fn is_finished(&self) -> bool {
+        false
+    }
+}
+
+/// A source serving observations held in memory, relative to the wall clock.
+pub struct InMemorySource {
+    observations: Mutex<Vec<UalfData>>,
+    finished: AtomicBool,
+}
+
+impl InMemorySource {
+    pub fn new(observations: Vec<UalfData>) -> InMemorySource {
+        InMemorySource {
+            observations: Mutex::new(observations),
+            finished: AtomicBool::new(false),
+        }
+    }
+
+    pub fn push(&self, observation: UalfData) {
+        self.observations.lock().unwrap().push(observation);
+    }
+
+    /// Marks the source finished, so the loops stop after their next poll.
+    pub fn finish(&self) {
+        self.finished.store(true, Ordering::Relaxed);
+    }
+}
+
+impl LightningSource for InMemorySource {
+    fn name(&self) -> &str {
+        "memory"
+    }
+
+    async fn fetch(&self, window: Duration) -> Result<Vec<UalfData>, SourceError> {
+        let now_ns = self.now_epoch_ns();
+        let oldest_ns = now_ns - window.as_nanos() as i64;
+        let observations = self.observations.lock().unwrap();
+        Ok(observations
+            .iter()
+            .filter(|obs| obs.epoch_ns > oldest_ns && obs.epoch_ns <= now_ns)
+            .cloned()
+            .collect())
+    }
+
+    fn health(&self) -> SourceHealth {
+        if self.is_finished() {
+            return SourceHealth::Down("source finished".to_string());
+        }
+        SourceHealth::Healthy
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,290 @@
+use std::time::Duration;
+
+use crate::{
+    db::{LocationForecast, UserLocation},
+    nowcast::uncertainty_km,
+    projection::AzimuthalEquidistant,
+    storm_tracker::TrackedStorm,
+};
+
+pub const FORECAST_HORIZON: Duration = Duration::from_secs(60 * 60);
+const FORECAST_STEP: Duration = Duration::from_secs(60);
+
+/// The distance from `point` to a polygon, or 0 when inside it. Coordinates
+/// are planar (north, east) km, and the polygon may be concave.
+fn distance_to_polygon(point: (f64, f64), polygon: &[(f64, f64)]) -> f64 {
+    // Even-odd rule, a ray due east crosses the edges an odd number of times
+    let crossings = polygon
+        .iter()
+        .zip(polygon.iter().cycle().skip(1))
+        .filter(|(a, b)| {
+            (a.0 > point.0) != (b.0 > point.0)
+                && point.1 < a.1 + (point.0 - a.0) / (b.0 - a.0) * (b.1 - a.1)
+        })
+        .count();
+    if polygon.len() >= 3 && crossings % 2 == 1 {
+        return 0.0;
+    }
+
+    polygon
+        .iter()
+        .zip(polygon.iter().cycle().skip(1))
+        .map(|(a, b)| {
+            let (dn, de) = (b.0 - a.0, b.1 - a.1);
+            let length_sq = dn * dn + de * de;
+            let t = if length_sq > 0.0 {
+                (((point.0 - a.0) * dn + (point.1 - a.1) * de) / length_sq).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            (point.0 - a.0 - t * dn).hypot(point.1 - a.1 - t * de)
+        })
+        .fold(f64::INFINITY, f64::min)
+}
+
+/// The complementary error function, accurate to about 1e-7 (Numerical
+/// Recipes `erfcc`).
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -z * z - 1.26551223
+        + t * (1.00002368
+            + t * (0.37409196
+                + t * (0.09678418
+                    + t * (-0.18628806
+                        + t * (0.27886807
+                            + t * (-1.13520398
+                                + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277))))))));
+    let result = t * poly.exp();
+    if x >= 0.0 {
+        result
+    } else {
+        2.0 - result
+    }
+}
+
+/// When the storm is expected to reach the location and how likely it is to
+/// do so within the horizon, as (eta_minutes, probability).
+///
+/// The location is followed back along the storm's motion relative to the
+/// hull from `DbscanCluster::hull`, one minute at a time. The forecast
+/// position error is treated as normally distributed with the nowcast
+/// uncertainty as standard deviation, and the probability is the best chance
+/// over the horizon that it carries the storm the remaining distance towards
+/// the location.
+fn storm_threat(
+    location: &UserLocation,
+    storm: &TrackedStorm,
+    hull: &[Vec<(f64, f64)>],
+) -> (Option<i64>, f64) {
+    let projection = AzimuthalEquidistant::new((location.latitude, location.longitude));
+    let hull: Vec<Vec<(f64, f64)>> = hull
+        .iter()
+        .filter(|polygon| !polygon.is_empty())
+        .map(|polygon| {
+            polygon
+                .iter()
+                .map(|vertex| projection.project(*vertex))
+                .collect()
+        })
+        .collect();
+    if hull.is_empty() {
+        return (None, 0.0);
+    }
+    let radius_km = location.radius_km as f64;
+    let (north_kmh, east_kmh) = storm
+        .motion
+        .map_or((0.0, 0.0), |motion| (motion.north_kmh, motion.east_kmh));
+
+    let mut eta_minutes = None;
+    let mut probability: f64 = 0.0;
+    let steps = FORECAST_HORIZON.as_secs() / FORECAST_STEP.as_secs();
+    for step in 0..=steps {
+        let hours = (step * FORECAST_STEP.as_secs()) as f64 / 3600.0;
+        let relative = (-north_kmh * hours, -east_kmh * hours);
+        let miss_km = hull
+            .iter()
+            .map(|polygon| distance_to_polygon(relative, polygon))
+            .fold(f64::INFINITY, f64::min)
+            - radius_km;
+        if miss_km <= 0.0 {
+            eta_minutes = Some((step * FORECAST_STEP.as_secs() / 60) as i64);
+            probability = 1.0;
+            break;
+        }
+        let sigma_km = uncertainty_km(storm.motion, hours);
+        if sigma_km > 0.0 {
+            // One-sided, only an error towards the location brings the storm closer
+            let towards = 0.5 * erfc(miss_km / (sigma_km * 2.0_f64.sqrt()));
+            probability = probability.max(towards);
+        }
+    }
+
+    (eta_minutes, probability)
+}
+
+/// The threat to a user location over the next `FORECAST_HORIZON`, combining
+/// every storm on the assumption that they arrive independently. `hulls`
+/// holds the hull of each storm.
+pub fn forecast_location(
+    location: &UserLocation,
+    storms: &[TrackedStorm],
+    hulls: &[Vec<Vec<(f64, f64)>>],
+) -> LocationForecast {
+    let mut forecast = LocationForecast {
+        location_id: location.id,
+        storm_id: None,
+        eta_minutes: None,
+        probability: 0.0,
+    };
+    let mut no_storm_probability = 1.0;
+    let mut likeliest = 0.0;
+
+    for (storm, hull) in storms.iter().zip(hulls) {
+        let (eta_minutes, probability) = storm_threat(location, storm, hull);
+        no_storm_probability *= 1.0 - probability;
+        if let Some(eta) = eta_minutes {
+            if forecast.eta_minutes.is_none_or(|earliest| eta < earliest) {
+                forecast.eta_minutes = Some(eta);
+                forecast.storm_id = Some(storm.storm_id);
+            }
+        } else if forecast.eta_minutes.is_none() && probability > likeliest {
+            likeliest = probability;
+            forecast.storm_id = Some(storm.storm_id);
+        }
+    }
+    forecast.probability = 1.0 - no_storm_probability;
+
+    forecast
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        dbscan::{DbscanCluster, HullAlgorithm},
+        storm_motion::MotionVector,
+        ualf::{strike_near, TEST_ORIGIN},
+    };
+
+    fn location(radius_km: i16) -> UserLocation {
+        UserLocation {
+            id: 7,
+            uuid: "location".to_string(),
+            latitude: TEST_ORIGIN.0,
+            longitude: TEST_ORIGIN.1,
+            radius_km,
+            all_clear_minutes: None,
+        }
+    }
+
+    // A storm of strikes within 2 km of the given offset from the location
+    fn storm(
+        storm_id: i64,
+        north_km: f64,
+        east_km: f64,
+        motion: Option<MotionVector>,
+    ) -> TrackedStorm {
+        let points = [(0.0, 0.0), (2.0, 0.0), (0.0, 2.0), (-2.0, 0.0), (0.0, -2.0)]
+            .iter()
+            .map(|(north, east)| strike_near(0, north_km + north, east_km + east))
+            .collect();
+        TrackedStorm {
+            storm_id,
+            cluster: DbscanCluster {
+                points,
+                cluster_id: 0,
+            },
+            motion,
+        }
+    }
+
+    // The hulls of the storms, as the prediction loop computes them
+    fn forecast_for(location: &UserLocation, storms: &[TrackedStorm]) -> LocationForecast {
+        let hulls: Vec<Vec<Vec<(f64, f64)>>> = storms
+            .iter()
+            .map(|storm| storm.cluster.hull(HullAlgorithm::Convex))
+            .collect();
+        forecast_location(location, storms, &hulls)
+    }
+
+    fn moving(north_kmh: f64, east_kmh: f64) -> Option<MotionVector> {
+        Some(MotionVector {
+            north_kmh,
+            east_kmh,
+        })
+    }
+
+    #[test]
+    fn a_storm_overhead_has_arrived() {
+        let forecast = forecast_for(&location(5), &[storm(1, 3.0, 0.0, None)]);
+        assert_eq!(forecast.storm_id, Some(1));
+        assert_eq!(forecast.eta_minutes, Some(0));
+        assert_eq!(forecast.probability, 1.0);
+    }
+
+    #[test]
+    fn a_stationary_storm_far_away_is_unlikely() {
+        let forecast = forecast_for(&location(5), &[storm(1, 30.0, 0.0, None)]);
+        assert_eq!(forecast.eta_minutes, None);
+        // 23 km from the radius, with 10 km of uncertainty after the hour
+        assert!(forecast.probability > 0.005 && forecast.probability < 0.02);
+    }
+
+    #[test]
+    fn an_approaching_storm_arrives_on_time() {
+        // The nearest strike is 28 km out, 23 km from the radius at 40 km/h
+        let forecast = forecast_for(&location(5), &[storm(1, 30.0, 0.0, moving(-40.0, 0.0))]);
+        assert_eq!(forecast.storm_id, Some(1));
+        let eta = forecast.eta_minutes.unwrap();
+        assert!((34..=36).contains(&eta), "{}", eta);
+        assert_eq!(forecast.probability, 1.0);
+    }
+
+    #[test]
+    fn a_storm_that_just_misses_is_a_coin_toss_at_most() {
+        // Passes 0.5 km outside the radius, 45 minutes from now
+        let forecast = forecast_for(&location(5), &[storm(1, 30.0, 7.5, moving(-40.0, 0.0))]);
+        assert_eq!(forecast.eta_minutes, None);
+        assert!(forecast.probability > 0.4 && forecast.probability < 0.5);
+    }
+
+    #[test]
+    fn a_receding_storm_is_unlikely() {
+        let forecast = forecast_for(&location(5), &[storm(1, 30.0, 0.0, moving(40.0, 0.0))]);
+        assert_eq!(forecast.eta_minutes, None);
+        assert!(forecast.probability < 0.01);
+    }
+
+    #[test]
+    fn the_first_arrival_wins() {
+        let storms = [
+            storm(1, 0.0, 50.0, moving(0.0, -40.0)),
+            storm(2, -30.0, 0.0, moving(40.0, 0.0)),
+            storm(3, 60.0, 0.0, None),
+        ];
+        let forecast = forecast_for(&location(5), &storms);
+        assert_eq!(forecast.storm_id, Some(2));
+        assert!(forecast.eta_minutes.unwrap() < 40);
+    }
+
+    #[test]
+    fn the_notch_of_a_concave_hull_is_outside_it() {
+        // A U opening north, 6 km wide with a 2 km wide notch down to 2 km
+        let u = [
+            (0.0, 0.0),
+            (0.0, 6.0),
+            (6.0, 6.0),
+            (6.0, 4.0),
+            (2.0, 4.0),
+            (2.0, 2.0),
+            (6.0, 2.0),
+            (6.0, 0.0),
+        ];
+        assert_eq!(distance_to_polygon((1.0, 3.0), &u), 0.0);
+        assert_eq!(distance_to_polygon((5.0, 1.0), &u), 0.0);
+        assert!((distance_to_polygon((5.0, 3.0), &u) - 1.0).abs() < 1e-9);
+        assert!((distance_to_polygon((3.0, 8.0), &u) - 2.0).abs() < 1e-9);
+    }
+}
@@ -33,5 +33,5 @@ pub fn get_observation_within_radius(
             epoch_ns: ualf_observation.epoch_ns,
         });
     }
-    return None;
+    None
 }
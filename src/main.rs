@@ -1,15 +1,26 @@
+use chrono::{DateTime, Utc};
 use dotenv::dotenv;
 use lightning_warning::{
-    db::{Database, Observation},
-    dbscan::{cluster_lightning, DbscanParams},
-    frost::{get_latest_10m_observations, get_latest_1h_observations, FrostError},
+    alert_state::AlertTracker,
+    db::{Database, LocationForecast, Observation, UserLocation},
+    dbscan::{cluster_lightning, DbscanParams, HullAlgorithm, HullParams},
+    frost::{parse_interval, FrostClient, FrostConfig, FrostError},
+    lightning_source::{LightningSource, SourceError},
+    location_forecast::forecast_location,
     location_utils::get_observation_within_radius,
+    region::Region,
+    storm_tracker::{StormTracker, TrackedStorm},
+    ualf::UalfData,
     ualf_buffer::UalfBuffer,
+    ualf_file::{append_ualf_file, read_ualf_files, ReplaySource},
 };
-use log::{error, info};
+use log::{error, info, warn};
 use reqwest::Error;
 use std::{
+    io,
+    path::{Path, PathBuf},
     process,
+    sync::Arc,
     thread::sleep,
     time::{Duration, Instant},
 };
@@ -18,23 +29,209 @@ use tokio::task;
 const POLLING_INTERVAL_SECONDS: u64 = 10;
 const ERROR_INTERVAL_SECONDS: u64 = POLLING_INTERVAL_SECONDS * 5;
 const PREDICITON_INTERVAL_SECONDS: u64 = 60;
+const REPLAY_WINDOW_NS: i64 = 3_600_000_000_000;
+const OBSERVATION_WINDOW: Duration = Duration::from_secs(10 * 60);
+const PREDICTION_WINDOW: Duration = Duration::from_secs(60 * 60);
+const DEFAULT_BUFFER_PATH: &str = "ualf_buffer.json";
+const BUFFER_CHECKPOINT_INTERVAL: Duration = Duration::from_secs(60);
 
-async fn observation_loop(frost_client: String, frost_secret: String, db: Database) {
-    let mut buffer = UalfBuffer::new();
+/// How long to wait after a failed fetch, or `None` when retrying is pointless.
+fn error_interval(e: &SourceError) -> Option<Duration> {
+    let error_interval = Duration::from_secs(ERROR_INTERVAL_SECONDS);
+    match e {
+        SourceError::Frost(FrostError::Unauthorized(_)) => {
+            error!("Frost rejected the credentials, check FROST_API_CLIENT and FROST_API_SECRET");
+            None
+        }
+        SourceError::Frost(FrostError::InvalidParameter(_)) => {
+            error!("Frost rejected the query, check FROST_REGION");
+            None
+        }
+        // Frost only waits out short rate limits itself and hands the rest back
+        SourceError::Frost(FrostError::RateLimited {
+            retry_after: Some(retry_after),
+            ..
+        }) => Some((*retry_after).max(error_interval)),
+        _ => Some(error_interval),
+    }
+}
 
-    loop {
-        info!("[OBSERVATION] getting latest 10 minutes of observations");
-        let ualf_observations = match get_latest_10m_observations(&frost_client, &frost_secret)
+async fn get_locations(db: &Database) -> Vec<UserLocation> {
+    info!("[OBSERVATION] Getting user locations");
+    let locations = db.get_locations().await.unwrap_or(vec![]);
+    info!("[OBSERVATION] {} user locations found", locations.len());
+    locations
+}
+
+async fn insert_observations_within_radius(
+    db: &Database,
+    locations: &[UserLocation],
+    ualf_observations: &[UalfData],
+) -> Vec<Observation> {
+    let mut observations_within_radius: Vec<Observation> = vec![];
+    for location in locations {
+        for ualf_observation in ualf_observations {
+            if let Some(ok) = get_observation_within_radius(ualf_observation, location) {
+                observations_within_radius.push(ok);
+            }
+        }
+    }
+    info!(
+        "[OBSERVATION] {} observations within radius",
+        observations_within_radius.len()
+    );
+    if !observations_within_radius.is_empty() {
+        info!("[OBSERVATION] inserting observations to db",);
+        db.insert_observations(&observations_within_radius)
             .await
-        {
+            .unwrap_or(());
+        info!("[OBSERVATION] observations inserted into db")
+    }
+    observations_within_radius
+}
+
+/// Moves user locations between warning and all-clear as of `now_ns`,
+/// persisting any change.
+async fn update_alerts(
+    db: &Database,
+    alerts: &mut AlertTracker,
+    now_ns: i64,
+    locations: &[UserLocation],
+    observations_within_radius: &[Observation],
+) {
+    let update = alerts.update(now_ns, locations, observations_within_radius);
+    if !update.alerts.is_empty() {
+        db.update_location_alerts(update.alerts, update.transitions)
+            .await
+            .unwrap_or(());
+    }
+}
+
+/// Clusters the observations into storms, recording merges and splits.
+async fn track_lightning_clusters(
+    db: &Database,
+    tracker: &mut StormTracker,
+    ualf_observations: &[UalfData],
+) -> Vec<TrackedStorm> {
+    info!("[PREDICTION] finding lightning clusters");
+    let now = Instant::now();
+    let result = cluster_lightning(ualf_observations, &DbscanParams::default());
+    let elapsed = now.elapsed().as_millis();
+    info!("[PREDICTION] dbscan algo took {:.2?}ms", elapsed);
+    info!(
+        "[PREDICTION] Found {} clusters and {} isolated strikes",
+        result.clusters.len(),
+        result.noise.len()
+    );
+
+    // Other processes, such as a replay, may have stored storms since the last run
+    if let Ok(Some(storm_id)) = db.get_max_storm_id().await {
+        tracker.resume_after(storm_id);
+    }
+    let update = tracker.update(result.clusters);
+    if !update.events.is_empty() {
+        db.insert_storm_events(&update.events).await.unwrap_or(());
+    }
+    update.storms
+}
+
+/// Tracks the latest storms, then publishes their forecasts for user
+/// locations and replaces the current prediction with them.
+async fn insert_lightning_clusters(
+    db: &Database,
+    tracker: &mut StormTracker,
+    hull: HullParams,
+    ualf_observations: &[UalfData],
+) {
+    let storms = track_lightning_clusters(db, tracker, ualf_observations).await;
+    let hulls: Vec<Vec<Vec<(f64, f64)>>> = storms
+        .iter()
+        .map(|storm| storm.cluster.hull(hull.algorithm))
+        .collect();
+
+    let locations = db.get_locations().await.unwrap_or(vec![]);
+    let forecasts: Vec<LocationForecast> = locations
+        .iter()
+        .map(|location| forecast_location(location, &storms, &hulls))
+        .collect();
+    info!(
+        "[PREDICTION] {} of {} user locations threatened within the hour",
+        forecasts.iter().filter(|f| f.probability > 0.0).count(),
+        forecasts.len()
+    );
+    if !forecasts.is_empty() {
+        db.upsert_location_forecasts(forecasts).await.unwrap_or(());
+    }
+    if !storms.is_empty() {
+        db.insert_prediction_and_remove_old(storms, &hulls, &hull)
+            .await
+            .unwrap_or(());
+    }
+}
+
+/// Restores the dedup buffer from its last checkpoint so a restart does not
+/// process the same strikes twice.
+fn load_buffer(buffer_path: Option<&Path>) -> UalfBuffer {
+    let Some(path) = buffer_path else {
+        return UalfBuffer::new(OBSERVATION_WINDOW);
+    };
+    match UalfBuffer::load(path, OBSERVATION_WINDOW) {
+        Ok(buffer) => {
+            info!(
+                "[OBSERVATION] restored {} processed observations from {}",
+                buffer.len(),
+                path.display()
+            );
+            buffer
+        }
+        Err(e) => {
+            if e.kind() != io::ErrorKind::NotFound {
+                warn!("Unable to restore buffer from {}: {}", path.display(), e);
+            }
+            UalfBuffer::new(OBSERVATION_WINDOW)
+        }
+    }
+}
+
+fn checkpoint_buffer(buffer: &UalfBuffer, path: &Path) {
+    match buffer.save(path) {
+        Ok(()) => info!("[OBSERVATION] buffer checkpointed to {}", path.display()),
+        Err(e) => warn!("Unable to checkpoint buffer to {}: {}", path.display(), e),
+    }
+}
+
+async fn observation_loop<S: LightningSource>(
+    source: Arc<S>,
+    db: Database,
+    buffer_path: Option<PathBuf>,
+    archive_path: Option<PathBuf>,
+) {
+    let mut buffer = load_buffer(buffer_path.as_deref());
+    let mut last_checkpoint = Instant::now();
+    let mut alerts = AlertTracker::new(db.get_location_alerts().await.unwrap_or(vec![]));
+
+    loop {
+        // Checked before fetching so the last fetch still covers the end of a replay
+        let finished = source.is_finished();
+        info!(
+            "[OBSERVATION] getting latest 10 minutes of observations from {}",
+            source.name()
+        );
+        let ualf_observations = match source.fetch(OBSERVATION_WINDOW).await {
             Ok(observations) => observations,
+            Err(SourceError::Frost(FrostError::NotFound(_))) => vec![],
             Err(e) => {
-                match e {
-                    FrostError::ApiError(msg) => error!("Failed to fetch observations: {}", msg),
-                    FrostError::RequestError(e) => error!("Frost API error: {}", e),
-                }
-                info!("sleeping for {} seconds", ERROR_INTERVAL_SECONDS);
-                sleep(Duration::from_secs(ERROR_INTERVAL_SECONDS));
+                error!("Failed to fetch observations: {}", e);
+                info!("{} source health: {:?}", source.name(), source.health());
+                let Some(interval) = error_interval(&e) else {
+                    error!("[OBSERVATION] stopping, fetching observations cannot succeed");
+                    if let Some(path) = &buffer_path {
+                        checkpoint_buffer(&buffer, path);
+                    }
+                    return;
+                };
+                info!("sleeping for {} seconds", interval.as_secs());
+                sleep(interval);
                 continue;
             }
         };
@@ -46,30 +243,40 @@ async fn observation_loop(frost_client: String, frost_secret: String, db: Databa
             unchecked_observations.len(),
             ualf_observations.len()
         );
+        if let Some(path) = &archive_path {
+            if let Err(e) = append_ualf_file(path, &unchecked_observations) {
+                warn!(
+                    "Unable to archive observations to {}: {}",
+                    path.display(),
+                    e
+                );
+            }
+        }
 
-        info!("[OBSERVATION] Getting user locations");
-        let locations = db.get_locations().await.unwrap_or(vec![]);
-        info!("[OBSERVATION] {} user locations found", locations.len());
+        let locations = get_locations(&db).await;
+        let observations_within_radius =
+            insert_observations_within_radius(&db, &locations, &unchecked_observations).await;
+        // A replay's clock is in the past, so the all-clear time is measured on it
+        let now_ns = source.now_epoch_ns();
+        update_alerts(
+            &db,
+            &mut alerts,
+            now_ns,
+            &locations,
+            &observations_within_radius,
+        )
+        .await;
 
-        let mut observations_within_radius: Vec<Observation> = vec![];
-        for location in &locations {
-            for ualf_observation in &unchecked_observations {
-                match get_observation_within_radius(ualf_observation, location) {
-                    Some(ok) => observations_within_radius.push(ok),
-                    None => (),
-                };
+        if let Some(path) = &buffer_path {
+            if finished || last_checkpoint.elapsed() >= BUFFER_CHECKPOINT_INTERVAL {
+                checkpoint_buffer(&buffer, path);
+                last_checkpoint = Instant::now();
             }
         }
-        info!(
-            "[OBSERVATION] {} observations within radius",
-            observations_within_radius.len()
-        );
-        if !observations_within_radius.is_empty() {
-            info!("[OBSERVATION] inserting observations to db",);
-            db.insert_observations(observations_within_radius)
-                .await
-                .unwrap_or(());
-            info!("[OBSERVATION] observations inserted into db")
+
+        if finished {
+            info!("[OBSERVATION] {} has finished, stopping", source.name());
+            return;
         }
         info!(
             "[OBSERVATION] sleeping for {} seconds",
@@ -79,23 +286,80 @@ async fn observation_loop(frost_client: String, frost_secret: String, db: Databa
     }
 }
 
-async fn prediction_loop(frost_client: String, frost_secret: String, db: Database) {
+async fn replay_archives(paths: Vec<String>, db: Database) {
+    info!("[REPLAY] reading {} UALF archives", paths.len());
+    let ualf_observations = match read_ualf_files(&paths) {
+        Ok(observations) => observations,
+        Err(e) => {
+            error!("Failed to read UALF archives: {}", e);
+            return;
+        }
+    };
+    info!("[REPLAY] Found {} observations", ualf_observations.len());
+
+    reprocess_observations(&db, &ualf_observations).await;
+}
+
+async fn backfill(frost: FrostClient, start: DateTime<Utc>, end: DateTime<Utc>, db: Database) {
+    info!("[REPLAY] fetching observations from {} to {}", start, end);
+    let mut ualf_observations = match frost.get_observations_between(start, end).await {
+        Ok(observations) => observations,
+        Err(e) => {
+            error!("Failed to fetch observations: {}", e);
+            return;
+        }
+    };
+    ualf_observations.sort_by_key(|obs| obs.epoch_ns);
+    info!("[REPLAY] Found {} observations", ualf_observations.len());
+
+    reprocess_observations(&db, &ualf_observations).await;
+}
+
+/// Runs historical observations through location matching, then tracks storms
+/// one hour at a time. Forecasts and predictions are left alone, as they are
+/// only meaningful for the storms of right now.
+async fn reprocess_observations(db: &Database, ualf_observations: &[UalfData]) {
+    let locations = get_locations(db).await;
+    insert_observations_within_radius(db, &locations, ualf_observations).await;
+
+    let mut tracker = StormTracker::default();
+    for window in ualf_observations
+        .chunk_by(|a, b| a.epoch_ns / REPLAY_WINDOW_NS == b.epoch_ns / REPLAY_WINDOW_NS)
+    {
+        info!(
+            "[REPLAY] clustering {} observations from {}",
+            window.len(),
+            window[0].timestamp()
+        );
+        track_lightning_clusters(db, &mut tracker, window).await;
+    }
+    info!("[REPLAY] done");
+}
+
+async fn prediction_loop<S: LightningSource>(source: Arc<S>, db: Database, hull: HullParams) {
+    let mut tracker = StormTracker::default();
     loop {
-        info!("[PREDICTION] getting latest 1 hour of observations");
-        let ualf_observations = match get_latest_1h_observations(&frost_client, &frost_secret).await
-        {
+        let finished = source.is_finished();
+        info!(
+            "[PREDICTION] getting latest 1 hour of observations from {}",
+            source.name()
+        );
+        let ualf_observations = match source.fetch(PREDICTION_WINDOW).await {
             Ok(observations) => observations,
+            Err(SourceError::Frost(FrostError::NotFound(_))) => vec![],
             Err(e) => {
-                match e {
-                    FrostError::ApiError(msg) => error!("Failed to fetch observations: {}", msg),
-                    FrostError::RequestError(e) => error!("Frost API error: {}", e),
-                }
-                info!("sleeping for {} seconds", ERROR_INTERVAL_SECONDS);
-                sleep(Duration::from_secs(ERROR_INTERVAL_SECONDS));
+                error!("Failed to fetch observations: {}", e);
+                info!("{} source health: {:?}", source.name(), source.health());
+                let Some(interval) = error_interval(&e) else {
+                    error!("[PREDICTION] stopping, fetching observations cannot succeed");
+                    return;
+                };
+                info!("sleeping for {} seconds", interval.as_secs());
+                sleep(interval);
                 continue;
             }
         };
-        if ualf_observations.is_empty() {
+        if ualf_observations.is_empty() && !finished {
             info!("[PREDICTION] No observations found the last hour");
             info!(
                 "[PREDICTION] sleeping for {} seconds",
@@ -108,23 +372,12 @@ async fn prediction_loop(frost_client: String, frost_secret: String, db: Databas
             "[PREDICTION] Found {} observations",
             ualf_observations.len()
         );
-        info!("[PREDICTION] finding lightning clusters");
-        let now = Instant::now();
-        let clustered_observations =
-            cluster_lightning(&ualf_observations, &DbscanParams::default());
-        let elapsed = now.elapsed().as_millis();
-        info!("[PREDICTION] dbscan algo took {:.2?}ms", elapsed);
-        info!(
-            "[PREDICTION] Found {} clusters",
-            clustered_observations.len()
-        );
+        insert_lightning_clusters(&db, &mut tracker, hull, &ualf_observations).await;
 
-        if !clustered_observations.is_empty() {
-            db.insert_prediction_and_remove_old(clustered_observations)
-                .await
-                .unwrap_or(());
+        if finished {
+            info!("[PREDICTION] {} has finished, stopping", source.name());
+            return;
         }
-
         info!(
             "[PREDICTION] sleeping for {} seconds",
             PREDICITON_INTERVAL_SECONDS
@@ -133,6 +386,24 @@ async fn prediction_loop(frost_client: String, frost_secret: String, db: Databas
     }
 }
 
+async fn run_loops<S: LightningSource + 'static>(
+    source: Arc<S>,
+    db: Database,
+    buffer_path: Option<PathBuf>,
+    archive_path: Option<PathBuf>,
+    hull: HullParams,
+) {
+    let observation_handle = task::spawn(observation_loop(
+        source.clone(),
+        db.clone(),
+        buffer_path,
+        archive_path,
+    ));
+    let prediction_handle = task::spawn(prediction_loop(source, db, hull));
+
+    tokio::try_join!(observation_handle, prediction_handle).unwrap();
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     dotenv().ok();
@@ -140,27 +411,140 @@ async fn main() -> Result<(), Error> {
 
     info!("My pid is {}", process::id());
 
-    let frost_client = std::env::var("FROST_API_CLIENT").expect("FROST_API_CLIENT must be set.");
-    let frost_secret = std::env::var("FROST_API_SECRET").expect("FROST_API_SECRET must be set.");
     let supabase_url = std::env::var("SUPABASE_URL").expect("SUPABASE_URL must be set.");
     let supabase_api =
         std::env::var("SUPABASE_API_SERVICE_ROLE").expect("SUPABASE_API_SERVICE_ROLE must be set.");
 
     let db: Database = Database::init(&supabase_url, &supabase_api);
 
-    let db_clone = db.clone();
-    let frost_client_prediction = frost_client.clone();
-    let frost_secret_prediction = frost_secret.clone();
+    let args: Vec<String> = std::env::args().skip(1).collect();
 
-    let observation_handle = task::spawn(observation_loop(frost_client, frost_secret, db));
+    let mut hull = HullParams::default();
+    if let Ok(algorithm) = std::env::var("HULL_ALGORITHM") {
+        hull.algorithm = HullAlgorithm::parse(&algorithm)
+            .expect("HULL_ALGORITHM must be convex, concave or concave:<alpha_km>.");
+    }
+    if let Ok(buffer_km) = std::env::var("HULL_BUFFER_KM") {
+        hull.buffer_km = buffer_km.parse().expect("HULL_BUFFER_KM must be a number.");
+    }
 
-    let prediction_handle = task::spawn(prediction_loop(
-        frost_client_prediction,
-        frost_secret_prediction,
-        db_clone,
-    ));
+    // UALF archives given on the command line are replayed instead of polling Frost
+    if !args.is_empty() && args[0] != "--backfill" {
+        match std::env::var("REPLAY_SPEED") {
+            Ok(speed) => {
+                let speed: f64 = speed.parse().expect("REPLAY_SPEED must be a number.");
+                let source = ReplaySource::from_files(&args, speed)
+                    .expect("UALF archives must be readable.");
+                run_loops(Arc::new(source), db, None, None, hull).await;
+            }
+            Err(_) => replay_archives(args, db).await,
+        }
+        return Ok(());
+    }
 
-    tokio::try_join!(observation_handle, prediction_handle).unwrap();
+    let frost_client = std::env::var("FROST_API_CLIENT").expect("FROST_API_CLIENT must be set.");
+    let frost_secret = std::env::var("FROST_API_SECRET").expect("FROST_API_SECRET must be set.");
+
+    let mut frost_config = FrostConfig::new(&frost_client, &frost_secret);
+    if let Ok(frost_url) = std::env::var("FROST_API_URL") {
+        frost_config.base_url = frost_url;
+    }
+    if let Ok(frost_region) = std::env::var("FROST_REGION") {
+        frost_config.region = Some(
+            Region::parse(&frost_region).expect("FROST_REGION must be a WKT polygon or bbox."),
+        );
+    }
+    let frost = FrostClient::new(frost_config).expect("Frost client must be constructible.");
+
+    if !args.is_empty() {
+        let (start, end) = args
+            .get(1)
+            .and_then(|interval| parse_interval(interval))
+            .expect("--backfill requires an interval such as 2024-07-01T00:00Z/2024-07-02T00:00Z.");
+        backfill(frost, start, end, db).await;
+        return Ok(());
+    }
+
+    let buffer_path =
+        std::env::var("UALF_BUFFER_PATH").unwrap_or_else(|_| DEFAULT_BUFFER_PATH.to_string());
+
+    let archive_path = std::env::var("UALF_ARCHIVE_PATH").ok().map(PathBuf::from);
+
+    run_loops(
+        Arc::new(frost),
+        db,
+        Some(PathBuf::from(buffer_path)),
+        archive_path,
+        hull,
+    )
+    .await;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use lightning_warning::lightning_source::InMemorySource;
+    use std::fs;
+
+    // Nothing listens on the discard port, so every database call fails fast
+    fn unreachable_db() -> Database {
+        Database::init("http://127.0.0.1:9", "test")
+    }
+
+    fn strike(age: Duration, latitude: f64, longitude: f64) -> UalfData {
+        let epoch_ns = Utc::now().timestamp_nanos_opt().unwrap() - age.as_nanos() as i64;
+        UalfData::synthetic(epoch_ns, latitude, longitude, -12)
+    }
+
+    #[tokio::test]
+    async fn observation_loop_archives_new_strikes_and_checkpoints_when_finished() {
+        let first = strike(Duration::from_secs(120), 60.0, 10.0);
+        let source = InMemorySource::new(vec![first.clone(), first.clone()]);
+        source.push(strike(Duration::from_secs(60), 60.01, 10.02));
+        source.push(strike(Duration::from_secs(30 * 60), 60.02, 10.04)); // Outside the window
+        source.finish();
+        let archive_path =
+            std::env::temp_dir().join(format!("observation_loop_{}.ualf", process::id()));
+        let buffer_path =
+            std::env::temp_dir().join(format!("observation_loop_{}.json", process::id()));
+        let _ = fs::remove_file(&archive_path);
+
+        let run = observation_loop(
+            Arc::new(source),
+            unreachable_db(),
+            Some(buffer_path.clone()),
+            Some(archive_path.clone()),
+        );
+        tokio::time::timeout(Duration::from_secs(30), run)
+            .await
+            .expect("observation loop must stop once the source has finished");
+
+        let archived = read_ualf_files(&[archive_path.to_str().unwrap().to_string()]);
+        fs::remove_file(&archive_path).unwrap();
+        assert_eq!(archived.unwrap().len(), 2);
+        let buffer = UalfBuffer::load(&buffer_path, OBSERVATION_WINDOW);
+        fs::remove_file(&buffer_path).unwrap();
+        assert_eq!(buffer.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn loops_stop_when_the_replay_finishes() {
+        let source = ReplaySource::new(vec![strike(Duration::from_secs(60), 60.0, 10.0)], 1.0);
+        sleep(Duration::from_millis(10));
+        assert!(source.is_finished());
+
+        let run = run_loops(
+            Arc::new(source),
+            unreachable_db(),
+            None,
+            None,
+            HullParams::default(),
+        );
+        tokio::time::timeout(Duration::from_secs(30), run)
+            .await
+            .expect("loops must stop once the replay has finished");
+    }
+}
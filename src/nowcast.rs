@@ -0,0 +1,191 @@
+use std::time::Duration;
+
+use crate::{dbscan::HullParams, storm_motion::MotionVector, storm_tracker::TrackedStorm};
+
+pub const LEAD_TIMES: [Duration; 3] = [
+    Duration::from_secs(15 * 60),
+    Duration::from_secs(30 * 60),
+    Duration::from_secs(60 * 60),
+];
+// Position error growth for a storm with no known motion
+const UNCERTAINTY_KMH: f64 = 10.0;
+// Extra position error growth per km/h of storm speed
+const UNCERTAINTY_PER_SPEED: f64 = 0.25;
+
+/// Where a storm is expected to be `lead_time` from now. Points are
+/// (latitude, longitude) pairs, and a concave outline may have several parts.
+#[derive(Debug, Clone)]
+pub struct Nowcast {
+    pub lead_time: Duration,
+    pub buffer_km: f64,
+    pub polygons: Vec<Vec<(f64, f64)>>,
+}
+
+/// How far off a storm's forecast position may be after `hours`.
+pub fn uncertainty_km(motion: Option<MotionVector>, hours: f64) -> f64 {
+    let speed_kmh = motion.map_or(0.0, |motion| motion.speed_kmh());
+    hours * (UNCERTAINTY_KMH + UNCERTAINTY_PER_SPEED * speed_kmh)
+}
+
+/// Moves the storm's hull, from `DbscanCluster::hull`, along its motion
+/// vector, then buffers it by the safety margin plus a radius that grows with
+/// lead time. Storms without a motion estimate are assumed to stay put.
+pub fn nowcast_storm(
+    storm: &TrackedStorm,
+    hull: &[Vec<(f64, f64)>],
+    lead_time: Duration,
+    params: &HullParams,
+) -> Nowcast {
+    let hours = lead_time.as_secs_f64() / 3600.0;
+    let buffer_km = params.buffer_km + uncertainty_km(storm.motion, hours);
+
+    let advected: Vec<Vec<(f64, f64)>> = hull
+        .iter()
+        .map(|polygon| {
+            polygon
+                .iter()
+                .map(|vertex| match storm.motion {
+                    Some(motion) => motion.advect(*vertex, hours),
+                    None => *vertex,
+                })
+                .collect()
+        })
+        .collect();
+
+    Nowcast {
+        lead_time,
+        buffer_km,
+        polygons: params.algorithm.buffer(&advected, buffer_km),
+    }
+}
+
+/// Nowcasts the storm at each of `LEAD_TIMES`.
+pub fn nowcast_storm_at_lead_times(
+    storm: &TrackedStorm,
+    hull: &[Vec<(f64, f64)>],
+    params: &HullParams,
+) -> Vec<Nowcast> {
+    LEAD_TIMES
+        .iter()
+        .map(|lead_time| nowcast_storm(storm, hull, *lead_time, params))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        dbscan::{DbscanCluster, HullAlgorithm},
+        projection::{polygon_area_km2, AzimuthalEquidistant},
+        ualf::{strike_near, TEST_ORIGIN},
+    };
+
+    const PARAMS: HullParams = HullParams {
+        algorithm: HullAlgorithm::Convex,
+        buffer_km: 5.0,
+    };
+
+    // A 10 km square of strikes centred on `TEST_ORIGIN`
+    fn storm(motion: Option<MotionVector>) -> TrackedStorm {
+        let corners = [(-5.0, -5.0), (-5.0, 5.0), (5.0, 5.0), (5.0, -5.0)];
+        TrackedStorm {
+            storm_id: 1,
+            cluster: DbscanCluster {
+                points: corners
+                    .iter()
+                    .map(|(north, east)| strike_near(0, *north, *east))
+                    .collect(),
+                cluster_id: 0,
+            },
+            motion,
+        }
+    }
+
+    // The mean of the outline's vertices in km from `TEST_ORIGIN`, which the
+    // rounded square's symmetry keeps at its centre
+    fn centre_km(nowcast: &Nowcast) -> (f64, f64) {
+        let projection = AzimuthalEquidistant::new(TEST_ORIGIN);
+        let vertices = &nowcast.polygons[0];
+        let sum = vertices.iter().fold((0.0, 0.0), |sum, vertex| {
+            let (north, east) = projection.project(*vertex);
+            (sum.0 + north, sum.1 + east)
+        });
+        (sum.0 / vertices.len() as f64, sum.1 / vertices.len() as f64)
+    }
+
+    #[test]
+    fn moves_the_hull_by_speed_times_lead_time_along_the_heading() {
+        // 40 km/h towards the north-east
+        let component_kmh = 40.0 / 2.0_f64.sqrt();
+        let storm = storm(Some(MotionVector {
+            north_kmh: component_kmh,
+            east_kmh: component_kmh,
+        }));
+        let hull = storm.cluster.hull(PARAMS.algorithm);
+
+        let nowcast = nowcast_storm(&storm, &hull, Duration::from_secs(30 * 60), &PARAMS);
+        let (north, east) = centre_km(&nowcast);
+        assert!((north.hypot(east) - 20.0).abs() < 0.2, "{} {}", north, east);
+        assert!((east.atan2(north).to_degrees() - 45.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn a_storm_without_motion_stays_put() {
+        let storm = storm(None);
+        let hull = storm.cluster.hull(PARAMS.algorithm);
+
+        let nowcast = nowcast_storm(&storm, &hull, Duration::from_secs(60 * 60), &PARAMS);
+        let (north, east) = centre_km(&nowcast);
+        assert!(north.hypot(east) < 0.2, "{} {}", north, east);
+        assert_eq!(nowcast.buffer_km, PARAMS.buffer_km + UNCERTAINTY_KMH);
+    }
+
+    #[test]
+    fn nowcasts_every_lead_time_with_a_growing_buffer() {
+        let storm = storm(Some(MotionVector {
+            north_kmh: 0.0,
+            east_kmh: 30.0,
+        }));
+        let hull = storm.cluster.hull(PARAMS.algorithm);
+
+        let nowcasts = nowcast_storm_at_lead_times(&storm, &hull, &PARAMS);
+        let lead_minutes: Vec<u64> = nowcasts
+            .iter()
+            .map(|nowcast| nowcast.lead_time.as_secs() / 60)
+            .collect();
+        assert_eq!(lead_minutes, vec![15, 30, 60]);
+        for pair in nowcasts.windows(2) {
+            assert!(pair[0].buffer_km > PARAMS.buffer_km);
+            assert!(pair[1].buffer_km > pair[0].buffer_km);
+            assert!(
+                polygon_area_km2(&pair[1].polygons[0]) > polygon_area_km2(&pair[0].polygons[0])
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_the_parts_of_a_concave_hull_apart() {
+        let params = HullParams {
+            algorithm: HullAlgorithm::Concave { alpha_km: 10.0 },
+            buffer_km: 2.0,
+        };
+        let square = storm(None).cluster.hull(HullAlgorithm::Convex)[0].clone();
+        let far_east: Vec<(f64, f64)> = square
+            .iter()
+            .map(|vertex| {
+                let projection = AzimuthalEquidistant::new(TEST_ORIGIN);
+                let (north, east) = projection.project(*vertex);
+                projection.unproject((north, east + 60.0))
+            })
+            .collect();
+
+        let nowcast = nowcast_storm(
+            &storm(None),
+            &[square, far_east],
+            Duration::from_secs(15 * 60),
+            &params,
+        );
+        assert_eq!(nowcast.polygons.len(), 2);
+    }
+}
@@ -0,0 +1,207 @@
+// Mean Earth radius in kilometers
+const EARTH_RADIUS_KM: f64 = 6371.0088;
+
+/// An azimuthal equidistant projection on the sphere. Distances and bearings
+/// from the center are true, so it is accurate for a storm-sized area around
+/// it. Geographic points are (latitude, longitude) pairs and projected ones
+/// are (north, east) pairs in kilometers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AzimuthalEquidistant {
+    center_latitude: f64,
+    center_longitude: f64,
+}
+
+impl AzimuthalEquidistant {
+    pub fn new(center: (f64, f64)) -> AzimuthalEquidistant {
+        AzimuthalEquidistant {
+            center_latitude: center.0.to_radians(),
+            center_longitude: center.1.to_radians(),
+        }
+    }
+
+    /// Centers the projection on the mean of `points`.
+    pub fn around(points: &[(f64, f64)]) -> AzimuthalEquidistant {
+        let count = points.len().max(1) as f64;
+        let sum = points
+            .iter()
+            .fold((0.0, 0.0), |acc, p| (acc.0 + p.0, acc.1 + p.1));
+        AzimuthalEquidistant::new((sum.0 / count, sum.1 / count))
+    }
+
+    pub fn project(&self, point: (f64, f64)) -> (f64, f64) {
+        let (lat0, lon0) = (self.center_latitude, self.center_longitude);
+        let latitude = point.0.to_radians();
+        let delta_longitude = point.1.to_radians() - lon0;
+
+        let cos_c = (lat0.sin() * latitude.sin()
+            + lat0.cos() * latitude.cos() * delta_longitude.cos())
+        .clamp(-1.0, 1.0);
+        let c = cos_c.acos();
+        let k = if c == 0.0 { 1.0 } else { c / c.sin() };
+
+        (
+            EARTH_RADIUS_KM
+                * k
+                * (lat0.cos() * latitude.sin()
+                    - lat0.sin() * latitude.cos() * delta_longitude.cos()),
+            EARTH_RADIUS_KM * k * latitude.cos() * delta_longitude.sin(),
+        )
+    }
+
+    pub fn unproject(&self, point: (f64, f64)) -> (f64, f64) {
+        let (lat0, lon0) = (self.center_latitude, self.center_longitude);
+        let (north, east) = point;
+        let rho = north.hypot(east);
+        if rho == 0.0 {
+            return (lat0.to_degrees(), lon0.to_degrees());
+        }
+
+        let c = rho / EARTH_RADIUS_KM;
+        let latitude = (c.cos() * lat0.sin() + north * c.sin() * lat0.cos() / rho)
+            .clamp(-1.0, 1.0)
+            .asin();
+        let longitude = lon0
+            + (east * c.sin()).atan2(rho * lat0.cos() * c.cos() - north * lat0.sin() * c.sin());
+
+        (latitude.to_degrees(), longitude.to_degrees())
+    }
+}
+
+/// The point `distance_km` from `point` along the great circle leaving it at
+/// `bearing_deg` clockwise from north.
+pub fn destination(point: (f64, f64), bearing_deg: f64, distance_km: f64) -> (f64, f64) {
+    let latitude = point.0.to_radians();
+    let longitude = point.1.to_radians();
+    let bearing = bearing_deg.to_radians();
+    let delta = distance_km / EARTH_RADIUS_KM;
+
+    let destination_latitude = (latitude.sin() * delta.cos()
+        + latitude.cos() * delta.sin() * bearing.cos())
+    .clamp(-1.0, 1.0)
+    .asin();
+    let destination_longitude = longitude
+        + (bearing.sin() * delta.sin() * latitude.cos())
+            .atan2(delta.cos() - latitude.sin() * destination_latitude.sin());
+
+    (
+        destination_latitude.to_degrees(),
+        destination_longitude.to_degrees(),
+    )
+}
+
+/// The great-circle distance between two points.
+pub fn distance_km(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (latitude_from, latitude_to) = (from.0.to_radians(), to.0.to_radians());
+    let half_delta_latitude = (latitude_to - latitude_from) / 2.0;
+    let half_delta_longitude = (to.1 - from.1).to_radians() / 2.0;
+
+    let a = half_delta_latitude.sin().powi(2)
+        + latitude_from.cos() * latitude_to.cos() * half_delta_longitude.sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().min(1.0).asin()
+}
+
+/// The length of a closed polygon's boundary along great circles.
+pub fn polygon_perimeter_km(polygon: &[(f64, f64)]) -> f64 {
+    if polygon.len() < 2 {
+        return 0.0;
+    }
+    polygon
+        .iter()
+        .zip(polygon.iter().cycle().skip(1))
+        .map(|(a, b)| distance_km(*a, *b))
+        .sum()
+}
+
+/// The area enclosed by a polygon on the sphere, whichever way it winds
+/// (Chamberlain and Duquette, "Some algorithms for polygons on a sphere").
+pub fn polygon_area_km2(polygon: &[(f64, f64)]) -> f64 {
+    if polygon.len() < 3 {
+        return 0.0;
+    }
+    let twice_area: f64 = polygon
+        .iter()
+        .zip(polygon.iter().cycle().skip(1))
+        .map(|(a, b)| {
+            let delta_longitude = (b.1 - a.1 + 540.0) % 360.0 - 180.0;
+            delta_longitude.to_radians() * (a.0.to_radians().sin() + b.0.to_radians().sin())
+        })
+        .sum();
+    (twice_area * EARTH_RADIUS_KM * EARTH_RADIUS_KM / 2.0).abs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn assert_same_point(a: (f64, f64), b: (f64, f64)) {
+        let delta_longitude = (a.1 - b.1 + 540.0).rem_euclid(360.0) - 180.0;
+        assert!(
+            (a.0 - b.0).abs() < 1e-9 && delta_longitude.abs() < 1e-9,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn unproject_inverts_project() {
+        let mut rng = StdRng::seed_from_u64(21);
+        for center in [
+            (0.0, 0.0),
+            (60.0, 10.0),
+            (69.0, 18.0),
+            (-45.0, 179.9),
+            (85.0, -120.0),
+        ] {
+            let projection = AzimuthalEquidistant::new(center);
+            assert_same_point(projection.unproject(projection.project(center)), center);
+            assert_eq!(projection.project(center), (0.0, 0.0));
+            for _ in 0..200 {
+                let point =
+                    destination(center, rng.gen_range(0.0..360.0), rng.gen_range(0.0..500.0));
+                assert_same_point(projection.unproject(projection.project(point)), point);
+            }
+        }
+    }
+
+    #[test]
+    fn distances_and_bearings_from_the_center_are_true() {
+        let projection = AzimuthalEquidistant::new((69.0, 18.0));
+        for bearing_deg in [0.0, 45.0, 90.0, 180.0, 300.0] {
+            let point = destination((69.0, 18.0), bearing_deg, 100.0);
+            let (north, east) = projection.project(point);
+            assert!((north.hypot(east) - 100.0).abs() < 1e-9);
+            assert!((east.atan2(north).to_degrees().rem_euclid(360.0) - bearing_deg).abs() < 1e-9);
+            assert!((distance_km((69.0, 18.0), point) - 100.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn a_degree_of_latitude_is_about_111_km() {
+        assert!((distance_km((60.0, 10.0), (61.0, 10.0)) - 111.195).abs() < 1e-3);
+        assert!((distance_km((60.0, 10.0), (60.0, 11.0)) - 55.597).abs() < 1e-2);
+        assert_eq!(distance_km((60.0, 10.0), (60.0, 10.0)), 0.0);
+    }
+
+    #[test]
+    fn area_and_perimeter_of_a_cell() {
+        let cell = [(60.0, 10.0), (60.0, 10.1), (60.1, 10.1), (60.1, 10.0)];
+        // Exact for a cell bounded by meridians and parallels
+        let expected_area = EARTH_RADIUS_KM
+            * EARTH_RADIUS_KM
+            * 0.1_f64.to_radians()
+            * (60.1_f64.to_radians().sin() - 60.0_f64.to_radians().sin());
+        assert!((polygon_area_km2(&cell) - expected_area).abs() < 1e-6);
+        let mut clockwise = cell;
+        clockwise.reverse();
+        assert_eq!(polygon_area_km2(&clockwise), polygon_area_km2(&cell));
+
+        let sides = 2.0 * distance_km(cell[0], cell[3])
+            + distance_km(cell[0], cell[1])
+            + distance_km(cell[2], cell[3]);
+        assert!((polygon_perimeter_km(&cell) - sides).abs() < 1e-9);
+        assert_eq!(polygon_area_km2(&cell[..2]), 0.0);
+    }
+}
@@ -0,0 +1,205 @@
+use crate::ualf::UalfData;
+
+/// A geographic area of interest. Points are (latitude, longitude) pairs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Region {
+    BoundingBox {
+        min_latitude: f64,
+        min_longitude: f64,
+        max_latitude: f64,
+        max_longitude: f64,
+    },
+    Polygon(Vec<(f64, f64)>),
+}
+
+impl Region {
+    /// Parses either a WKT polygon, `POLYGON((lon lat, lon lat, ...))`, or a
+    /// bounding box given as `min_lon,min_lat,max_lon,max_lat`.
+    pub fn parse(region_str: &str) -> Option<Region> {
+        let region_str = region_str.trim();
+        if let Some(ring) = region_str
+            .to_uppercase()
+            .strip_prefix("POLYGON")
+            .map(|rest| {
+                rest.trim()
+                    .trim_start_matches('(')
+                    .trim_end_matches(')')
+                    .to_string()
+            })
+        {
+            let mut points: Vec<(f64, f64)> = vec![];
+            for pair in ring.split(',') {
+                let mut coordinates = pair.split_whitespace().map(|v| v.parse::<f64>());
+                let longitude = coordinates.next()?.ok()?;
+                let latitude = coordinates.next()?.ok()?;
+                points.push((latitude, longitude));
+            }
+            if points.len() < 3 {
+                return None;
+            }
+            return Some(Region::Polygon(points));
+        }
+
+        let values: Vec<f64> = region_str
+            .split(',')
+            .map(|v| v.trim().parse::<f64>())
+            .collect::<Result<_, _>>()
+            .ok()?;
+        if values.len() != 4 || values[0] >= values[2] || values[1] >= values[3] {
+            return None;
+        }
+        Some(Region::BoundingBox {
+            min_longitude: values[0],
+            min_latitude: values[1],
+            max_longitude: values[2],
+            max_latitude: values[3],
+        })
+    }
+
+    pub fn contains(&self, latitude: f64, longitude: f64) -> bool {
+        match self {
+            Region::BoundingBox {
+                min_latitude,
+                min_longitude,
+                max_latitude,
+                max_longitude,
+            } => {
+                (*min_latitude..=*max_latitude).contains(&latitude)
+                    && (*min_longitude..=*max_longitude).contains(&longitude)
+            }
+            Region::Polygon(points) => {
+                // Ray casting along the latitude axis
+                let mut inside = false;
+                let mut j = points.len() - 1;
+                for i in 0..points.len() {
+                    let (lat_i, lon_i) = points[i];
+                    let (lat_j, lon_j) = points[j];
+                    if (lat_i > latitude) != (lat_j > latitude)
+                        && longitude
+                            < (lon_j - lon_i) * (latitude - lat_i) / (lat_j - lat_i) + lon_i
+                    {
+                        inside = !inside;
+                    }
+                    j = i;
+                }
+                inside
+            }
+        }
+    }
+
+    pub fn contains_observation(&self, observation: &UalfData) -> bool {
+        self.contains(observation.latitude, observation.longitude)
+    }
+
+    /// Formats the region as a closed WKT polygon, as used by the Frost
+    /// `geometry` parameter.
+    pub fn to_wkt(&self) -> String {
+        let points = match self {
+            Region::BoundingBox {
+                min_latitude,
+                min_longitude,
+                max_latitude,
+                max_longitude,
+            } => vec![
+                (*min_latitude, *min_longitude),
+                (*min_latitude, *max_longitude),
+                (*max_latitude, *max_longitude),
+                (*max_latitude, *min_longitude),
+            ],
+            Region::Polygon(points) => points.clone(),
+        };
+
+        let mut ring: Vec<String> = points
+            .iter()
+            .map(|(latitude, longitude)| format!("{} {}", longitude, latitude))
+            .collect();
+        if points.first() != points.last() {
+            ring.push(ring[0].clone());
+        }
+        format!("POLYGON(({}))", ring.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Southern Norway, as a triangle with longitude first like WKT
+    const TRIANGLE: &str = "POLYGON((5 58, 12 58, 8 63, 5 58))";
+
+    #[test]
+    fn parses_wkt_with_longitude_first() {
+        let expected = Region::Polygon(vec![(58.0, 5.0), (58.0, 12.0), (63.0, 8.0), (58.0, 5.0)]);
+        assert_eq!(Region::parse(TRIANGLE), Some(expected.clone()));
+        assert_eq!(
+            Region::parse("  polygon (( 5 58,12 58, 8 63 , 5 58 )) "),
+            Some(expected)
+        );
+    }
+
+    #[test]
+    fn parses_a_bounding_box_with_longitude_first() {
+        assert_eq!(
+            Region::parse("4.5, 57.5, 31.5, 71.5"),
+            Some(Region::BoundingBox {
+                min_latitude: 57.5,
+                min_longitude: 4.5,
+                max_latitude: 71.5,
+                max_longitude: 31.5,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_regions() {
+        for region in [
+            "",
+            "POLYGON((5 58, 12 58))",
+            "POLYGON((5 58, 12, 8 63))",
+            "POLYGON((5 58, 12 north, 8 63))",
+            "4.5,57.5,31.5",
+            "4.5,57.5,31.5,71.5,0",
+            "31.5,57.5,4.5,71.5",
+            "4.5,71.5,31.5,57.5",
+            "4.5,57.5,east,71.5",
+        ] {
+            assert_eq!(Region::parse(region), None, "{}", region);
+        }
+    }
+
+    #[test]
+    fn contains_points_inside_only() {
+        let bbox = Region::parse("4.5,57.5,31.5,71.5").unwrap();
+        assert!(bbox.contains(60.0, 10.0));
+        assert!(bbox.contains(57.5, 4.5));
+        assert!(!bbox.contains(10.0, 60.0));
+        assert!(!bbox.contains(72.0, 10.0));
+
+        let triangle = Region::parse(TRIANGLE).unwrap();
+        assert!(triangle.contains(59.0, 8.0));
+        assert!(!triangle.contains(62.0, 6.0));
+        assert!(!triangle.contains(8.0, 59.0));
+        assert!(!triangle.contains(57.0, 8.0));
+    }
+
+    #[test]
+    fn formats_a_closed_ring_with_longitude_first() {
+        assert_eq!(
+            Region::parse("4.5,57.5,31.5,71.5").unwrap().to_wkt(),
+            "POLYGON((4.5 57.5, 31.5 57.5, 31.5 71.5, 4.5 71.5, 4.5 57.5))"
+        );
+        // A closed ring is not closed twice, an open one is closed
+        assert_eq!(
+            Region::parse(TRIANGLE).unwrap().to_wkt(),
+            "POLYGON((5 58, 12 58, 8 63, 5 58))"
+        );
+        assert_eq!(
+            Region::parse("POLYGON((5 58, 12 58, 8 63))")
+                .unwrap()
+                .to_wkt(),
+            "POLYGON((5 58, 12 58, 8 63, 5 58))"
+        );
+        let region = Region::parse(TRIANGLE).unwrap();
+        assert_eq!(Region::parse(&region.to_wkt()), Some(region));
+    }
+}
@@ -0,0 +1,118 @@
+use crate::{projection::AzimuthalEquidistant, ualf::UalfData};
+
+const NS_PER_HOUR: f64 = 3_600_000_000_000.0;
+// Strikes this much older than the newest one count half in the regression
+const STRIKE_HALF_LIFE_NS: f64 = 15.0 * 60.0 * 1e9;
+// Shorter spans give too noisy a slope to be useful
+const MIN_SPAN_NS: i64 = 5 * 60 * 1_000_000_000;
+// Faster than any thunderstorm, so the fit is picking up something else
+const MAX_SPEED_KMH: f64 = 150.0;
+
+/// The velocity of a storm over the ground.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MotionVector {
+    pub north_kmh: f64,
+    pub east_kmh: f64,
+}
+
+impl MotionVector {
+    pub fn speed_kmh(&self) -> f64 {
+        self.north_kmh.hypot(self.east_kmh)
+    }
+
+    /// The direction the storm is moving towards, in degrees clockwise from north.
+    pub fn heading_deg(&self) -> f64 {
+        self.east_kmh
+            .atan2(self.north_kmh)
+            .to_degrees()
+            .rem_euclid(360.0)
+    }
+
+    /// Where a point moving with this velocity is after `hours`.
+    pub fn advect(&self, point: (f64, f64), hours: f64) -> (f64, f64) {
+        AzimuthalEquidistant::new(point).unproject((self.north_kmh * hours, self.east_kmh * hours))
+    }
+
+    pub fn average(&self, other: &MotionVector) -> MotionVector {
+        MotionVector {
+            north_kmh: (self.north_kmh + other.north_kmh) / 2.0,
+            east_kmh: (self.east_kmh + other.east_kmh) / 2.0,
+        }
+    }
+}
+
+/// Fits position against time by weighted least squares. Samples are
+/// (epoch_ns, (latitude, longitude), weight).
+fn fit_velocity(samples: &[(i64, (f64, f64), f64)]) -> Option<MotionVector> {
+    let first = samples.iter().map(|s| s.0).min()?;
+    let last = samples.iter().map(|s| s.0).max()?;
+    if last - first < MIN_SPAN_NS {
+        return None;
+    }
+
+    // Project onto a plane around the first sample, in km north and east
+    let projection = AzimuthalEquidistant::new(samples[0].1);
+    let projected: Vec<(f64, f64, f64, f64)> = samples
+        .iter()
+        .map(|(epoch_ns, point, weight)| {
+            let (north, east) = projection.project(*point);
+            (
+                (epoch_ns - first) as f64 / NS_PER_HOUR,
+                north,
+                east,
+                *weight,
+            )
+        })
+        .collect();
+
+    let total_weight: f64 = projected.iter().map(|p| p.3).sum();
+    let mean = projected.iter().fold((0.0, 0.0, 0.0), |acc, (t, n, e, w)| {
+        (acc.0 + w * t, acc.1 + w * n, acc.2 + w * e)
+    });
+    let (mean_t, mean_n, mean_e) = (
+        mean.0 / total_weight,
+        mean.1 / total_weight,
+        mean.2 / total_weight,
+    );
+
+    let (mut var_t, mut cov_n, mut cov_e) = (0.0, 0.0, 0.0);
+    for (t, n, e, w) in &projected {
+        var_t += w * (t - mean_t) * (t - mean_t);
+        cov_n += w * (t - mean_t) * (n - mean_n);
+        cov_e += w * (t - mean_t) * (e - mean_e);
+    }
+    if var_t <= 0.0 {
+        return None;
+    }
+
+    let motion = MotionVector {
+        north_kmh: cov_n / var_t,
+        east_kmh: cov_e / var_t,
+    };
+    (motion.speed_kmh() <= MAX_SPEED_KMH).then_some(motion)
+}
+
+/// Estimates motion from successive cluster centroids, given as
+/// (epoch_ns, (latitude, longitude)).
+pub fn centroid_motion(history: &[(i64, (f64, f64))]) -> Option<MotionVector> {
+    let samples: Vec<(i64, (f64, f64), f64)> = history
+        .iter()
+        .map(|(epoch_ns, center)| (*epoch_ns, *center, 1.0))
+        .collect();
+    fit_velocity(&samples)
+}
+
+/// Estimates motion from the strikes of a single cluster, weighting recent
+/// strikes more since the active part of a storm is at its leading edge.
+pub fn strike_motion(points: &[UalfData]) -> Option<MotionVector> {
+    let newest = points.iter().map(|p| p.epoch_ns).max()?;
+    let samples: Vec<(i64, (f64, f64), f64)> = points
+        .iter()
+        .map(|p| {
+            let age_ns = (newest - p.epoch_ns) as f64;
+            let weight = 0.5_f64.powf(age_ns / STRIKE_HALF_LIFE_NS);
+            (p.epoch_ns, (p.latitude, p.longitude), weight)
+        })
+        .collect();
+    fit_velocity(&samples)
+}
@@ -0,0 +1,356 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+
+use log::info;
+
+use crate::{
+    dbscan::DbscanCluster,
+    projection::distance_km,
+    storm_motion::{centroid_motion, strike_motion, MotionVector},
+    ualf_buffer::ObservationKey,
+};
+
+const DEFAULT_MAX_MATCH_KM: f64 = 30.0;
+// How far back centroids are kept for estimating motion
+const CENTROID_HISTORY_NS: i64 = 60 * 60 * 1_000_000_000;
+
+pub type StormId = i64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StormEvent {
+    Merge {
+        storm_id: StormId,    // The storm that lives on
+        merged: Vec<StormId>, // Storms that were absorbed into it
+    },
+    Split {
+        storm_id: StormId,        // The storm that broke up
+        split_into: Vec<StormId>, // New storms that broke off from it
+    },
+}
+
+/// A cluster from the latest prediction run with its persistent storm ID.
+#[derive(Debug)]
+pub struct TrackedStorm {
+    pub storm_id: StormId,
+    pub cluster: DbscanCluster,
+    pub motion: Option<MotionVector>,
+}
+
+#[derive(Debug)]
+pub struct TrackingUpdate {
+    pub storms: Vec<TrackedStorm>,
+    pub events: Vec<StormEvent>,
+}
+
+/// What is remembered about a storm between prediction runs.
+struct PreviousStorm {
+    storm_id: StormId,
+    center: (f64, f64),
+    strikes: HashSet<ObservationKey>,
+    history: Vec<(i64, (f64, f64))>, // Centroid at the newest strike of each run
+}
+
+/// Gives clusters stable storm IDs across prediction runs.
+///
+/// Consecutive runs cluster overlapping windows, so a new cluster is matched
+/// to the previous storms it shares strikes with. A cluster sharing no strikes
+/// falls back to the nearest previous centroid within `max_match_km`. When
+/// several clusters claim the same storm the best match keeps its ID, and the
+/// others are reported as merges or splits.
+pub struct StormTracker {
+    previous: Vec<PreviousStorm>,
+    next_storm_id: StormId,
+    pub max_match_km: f64,
+}
+
+impl Default for StormTracker {
+    fn default() -> Self {
+        StormTracker::new(DEFAULT_MAX_MATCH_KM)
+    }
+}
+
+impl StormTracker {
+    pub fn new(max_match_km: f64) -> StormTracker {
+        StormTracker {
+            previous: vec![],
+            next_storm_id: 1,
+            max_match_km,
+        }
+    }
+
+    /// Makes sure new storms get IDs above `storm_id`, such as the highest one
+    /// stored by this or another process.
+    pub fn resume_after(&mut self, storm_id: StormId) {
+        self.next_storm_id = self.next_storm_id.max(storm_id + 1);
+    }
+
+    pub fn update(&mut self, clusters: Vec<DbscanCluster>) -> TrackingUpdate {
+        let strikes: Vec<HashSet<ObservationKey>> = clusters
+            .iter()
+            .map(|cluster| cluster.points.iter().map(ObservationKey::new).collect())
+            .collect();
+        let centers: Vec<(f64, f64)> = clusters.iter().map(|c| c.center()).collect();
+
+        // Links between new clusters and previous storms, best matches first
+        let mut overlap_links: Vec<(usize, usize, usize)> = vec![];
+        let mut distance_links: Vec<(usize, usize, f64)> = vec![];
+        for (new_idx, new_strikes) in strikes.iter().enumerate() {
+            let before = overlap_links.len();
+            for (prev_idx, previous) in self.previous.iter().enumerate() {
+                let shared = new_strikes.intersection(&previous.strikes).count();
+                if shared > 0 {
+                    overlap_links.push((new_idx, prev_idx, shared));
+                }
+            }
+            if overlap_links.len() > before {
+                continue;
+            }
+
+            let nearest = self
+                .previous
+                .iter()
+                .enumerate()
+                .map(|(prev_idx, previous)| {
+                    (prev_idx, distance_km(centers[new_idx], previous.center))
+                })
+                .filter(|(_, distance)| *distance <= self.max_match_km)
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            if let Some((prev_idx, distance)) = nearest {
+                distance_links.push((new_idx, prev_idx, distance));
+            }
+        }
+        overlap_links.sort_by_key(|(_, _, shared)| Reverse(*shared));
+        distance_links.sort_by(|a, b| a.2.total_cmp(&b.2));
+        let links: Vec<(usize, usize)> = overlap_links
+            .iter()
+            .map(|(new_idx, prev_idx, _)| (*new_idx, *prev_idx))
+            .chain(
+                distance_links
+                    .iter()
+                    .map(|(new_idx, prev_idx, _)| (*new_idx, *prev_idx)),
+            )
+            .collect();
+
+        // Each previous storm ID goes to its best matching cluster
+        let mut storm_ids: Vec<Option<StormId>> = vec![None; clusters.len()];
+        let mut histories: Vec<Vec<(i64, (f64, f64))>> = vec![vec![]; clusters.len()];
+        let mut claimed: HashSet<usize> = HashSet::new();
+        for (new_idx, prev_idx) in &links {
+            if storm_ids[*new_idx].is_none() && claimed.insert(*prev_idx) {
+                storm_ids[*new_idx] = Some(self.previous[*prev_idx].storm_id);
+                histories[*new_idx] = self.previous[*prev_idx].history.clone();
+            }
+        }
+        for ((history, cluster), center) in histories.iter_mut().zip(&clusters).zip(&centers) {
+            let newest = cluster.points.iter().map(|p| p.epoch_ns).max().unwrap_or(0);
+            history.retain(|(epoch_ns, _)| newest - epoch_ns <= CENTROID_HISTORY_NS);
+            history.push((newest, *center));
+        }
+        let storm_ids: Vec<StormId> = storm_ids
+            .into_iter()
+            .map(|storm_id| {
+                storm_id.unwrap_or_else(|| {
+                    self.next_storm_id += 1;
+                    self.next_storm_id - 1
+                })
+            })
+            .collect();
+
+        let mut predecessors: HashMap<usize, Vec<StormId>> = HashMap::new();
+        let mut successors: HashMap<usize, Vec<StormId>> = HashMap::new();
+        for (new_idx, prev_idx) in &links {
+            predecessors
+                .entry(*new_idx)
+                .or_default()
+                .push(self.previous[*prev_idx].storm_id);
+            successors
+                .entry(*prev_idx)
+                .or_default()
+                .push(storm_ids[*new_idx]);
+        }
+
+        let mut events: Vec<StormEvent> = vec![];
+        for (new_idx, storm_id) in storm_ids.iter().enumerate() {
+            let mut merged = predecessors.remove(&new_idx).unwrap_or_default();
+            if merged.len() > 1 {
+                merged.retain(|id| id != storm_id);
+                merged.sort_unstable();
+                info!(
+                    "[TRACKER] storms {:?} merged into storm #{}",
+                    merged, storm_id
+                );
+                events.push(StormEvent::Merge {
+                    storm_id: *storm_id,
+                    merged,
+                });
+            }
+        }
+        for (prev_idx, previous) in self.previous.iter().enumerate() {
+            let mut split_into = successors.remove(&prev_idx).unwrap_or_default();
+            if split_into.len() > 1 {
+                split_into.retain(|id| *id != previous.storm_id);
+                split_into.sort_unstable();
+                info!(
+                    "[TRACKER] storm #{} split into {:?}",
+                    previous.storm_id, split_into
+                );
+                events.push(StormEvent::Split {
+                    storm_id: previous.storm_id,
+                    split_into,
+                });
+            }
+        }
+
+        let storms = storm_ids
+            .iter()
+            .zip(clusters)
+            .zip(&histories)
+            .map(|((storm_id, cluster), history)| {
+                // Centroids lag behind a moving storm, strikes are noisy, so blend both
+                let motion = match (centroid_motion(history), strike_motion(&cluster.points)) {
+                    (Some(centroid), Some(strikes)) => Some(centroid.average(&strikes)),
+                    (centroid, strikes) => centroid.or(strikes),
+                };
+                TrackedStorm {
+                    storm_id: *storm_id,
+                    cluster,
+                    motion,
+                }
+            })
+            .collect();
+
+        self.previous = storm_ids
+            .into_iter()
+            .zip(centers)
+            .zip(strikes)
+            .zip(histories)
+            .map(|(((storm_id, center), strikes), history)| PreviousStorm {
+                storm_id,
+                center,
+                strikes,
+                history,
+            })
+            .collect();
+
+        TrackingUpdate { storms, events }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::ualf::{strike_near, UalfData};
+
+    const MINUTE_NS: i64 = 60 * 1_000_000_000;
+
+    // A small cell of strikes, one per minute from `start_minute`
+    fn cell(start_minute: i64, count: i64, north_km: f64, east_km: f64) -> Vec<UalfData> {
+        (0..count)
+            .map(|i| {
+                let offset = (i % 3) as f64;
+                strike_near(
+                    (start_minute + i) * MINUTE_NS,
+                    north_km + offset,
+                    east_km - offset,
+                )
+            })
+            .collect()
+    }
+
+    fn cluster(points: Vec<UalfData>) -> DbscanCluster {
+        DbscanCluster {
+            points,
+            cluster_id: 0,
+        }
+    }
+
+    fn ids(update: &TrackingUpdate) -> Vec<StormId> {
+        update.storms.iter().map(|storm| storm.storm_id).collect()
+    }
+
+    #[test]
+    fn keeps_the_id_of_a_cluster_sharing_strikes() {
+        let mut tracker = StormTracker::default();
+        let first = tracker.update(vec![cluster(cell(0, 6, 0.0, 0.0))]);
+        let mut points = cell(0, 6, 0.0, 0.0)[3..].to_vec();
+        points.extend(cell(6, 6, 2.0, 2.0));
+        let second = tracker.update(vec![cluster(points)]);
+
+        assert_eq!(ids(&first), vec![1]);
+        assert_eq!(ids(&second), vec![1]);
+        assert!(second.events.is_empty());
+    }
+
+    #[test]
+    fn falls_back_to_the_nearest_centroid() {
+        let mut tracker = StormTracker::default();
+        tracker.update(vec![cluster(cell(0, 6, 0.0, 0.0))]);
+        let nearby = tracker.update(vec![cluster(cell(10, 6, 5.0, 5.0))]);
+        let far = tracker.update(vec![cluster(cell(20, 6, 100.0, 0.0))]);
+
+        assert_eq!(ids(&nearby), vec![1]);
+        assert_eq!(ids(&far), vec![2]);
+    }
+
+    #[test]
+    fn reports_merges() {
+        let mut tracker = StormTracker::default();
+        let west = cell(0, 8, 0.0, 0.0);
+        let east = cell(0, 4, 0.0, 15.0);
+        let before = tracker.update(vec![cluster(west.clone()), cluster(east.clone())]);
+        let merged = tracker.update(vec![cluster([west, east].concat())]);
+
+        assert_eq!(ids(&before), vec![1, 2]);
+        assert_eq!(ids(&merged), vec![1]);
+        assert_eq!(
+            merged.events,
+            vec![StormEvent::Merge {
+                storm_id: 1,
+                merged: vec![2],
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_splits() {
+        let mut tracker = StormTracker::default();
+        let north = cell(0, 8, 10.0, 0.0);
+        let south = cell(0, 4, -10.0, 0.0);
+        tracker.update(vec![cluster([north.clone(), south.clone()].concat())]);
+        let split = tracker.update(vec![cluster(south), cluster(north)]);
+
+        assert_eq!(ids(&split), vec![2, 1]);
+        assert_eq!(
+            split.events,
+            vec![StormEvent::Split {
+                storm_id: 1,
+                split_into: vec![2],
+            }]
+        );
+    }
+
+    #[test]
+    fn follows_a_storm_moving_across_the_map() {
+        let mut tracker = StormTracker::default();
+        tracker.resume_after(41);
+
+        // 40 km/h east, clustered every 5 minutes over the latest 15 minutes of strikes
+        let strikes_until = |newest_minute: i64| -> Vec<UalfData> {
+            (newest_minute - 15..newest_minute)
+                .map(|minute| {
+                    let north_km = minute.rem_euclid(3) as f64;
+                    strike_near(minute * MINUTE_NS, north_km, minute as f64 * 40.0 / 60.0)
+                })
+                .collect()
+        };
+        for run in 0..12 {
+            let update = tracker.update(vec![cluster(strikes_until(run * 5))]);
+            assert_eq!(ids(&update), vec![42]);
+
+            let motion = update.storms[0].motion.unwrap();
+            assert!((motion.speed_kmh() - 40.0).abs() < 2.0, "{:?}", motion);
+            assert!((motion.heading_deg() - 90.0).abs() < 5.0, "{:?}", motion);
+        }
+    }
+}
@@ -1,44 +1,396 @@
-use chrono::NaiveDate;
+use std::error;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, NaiveDate, Timelike, Utc};
+use log::{debug, warn};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+const UALF_COLUMN_COUNT: usize = 25;
+
+#[derive(Debug, PartialEq)]
+pub enum UalfParseError {
+    ColumnCount(usize),
+    InvalidNumber { column: usize, value: String },
+    InvalidDate,
+    CoordinatesOutOfRange { latitude: f64, longitude: f64 },
+}
+
+impl fmt::Display for UalfParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UalfParseError::ColumnCount(count) => {
+                write!(f, "expected {} columns, found {}", UALF_COLUMN_COUNT, count)
+            }
+            UalfParseError::InvalidNumber { column, value } => {
+                write!(f, "invalid number '{}' in column {}", value, column)
+            }
+            UalfParseError::InvalidDate => write!(f, "invalid date or time"),
+            UalfParseError::CoordinatesOutOfRange {
+                latitude,
+                longitude,
+            } => write!(f, "coordinates out of range: ({}, {})", latitude, longitude),
+        }
+    }
+}
+
+impl error::Error for UalfParseError {}
+
+fn parse_column<T: FromStr>(columns: &[&str], column: usize) -> Result<T, UalfParseError> {
+    columns[column]
+        .parse::<T>()
+        .map_err(|_| UalfParseError::InvalidNumber {
+            column,
+            value: columns[column].to_string(),
+        })
+}
+
+/// A single lightning observation in the Universal ASCII Lightning Format.
+///
+/// Every column of the UALF record is kept, with the seven date/time columns
+/// collapsed into `epoch_ns`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct UalfData {
+    pub version: u8,
     pub epoch_ns: i64,
     pub latitude: f64,
     pub longitude: f64,
-    pub peak_current: i16,
+    pub peak_current: i16, // kA, sign gives polarity
+    pub multiplicity: u8,  // 0 for strokes, 1-99 for flashes
+    pub sensors: u16,
+    pub degrees_of_freedom: u16,
+    pub ellipse_angle: f64,      // Degrees clockwise from north
+    pub semi_major_axis_km: f64, // 50% confidence ellipse
+    pub semi_minor_axis_km: f64,
+    pub chi_square: f64,
+    pub rise_time_us: f64,
+    pub peak_to_zero_time_us: f64,
+    pub max_rate_of_rise: f64, // kA/us
     pub cloud_indicator: bool,
+    pub angle_indicator: bool,
+    pub signal_indicator: bool,
+    pub timing_indicator: bool,
 }
 
 impl UalfData {
-    pub fn from_string(ualf_str: &str) -> Option<UalfData> {
-        let split_observation: Vec<f64> = ualf_str
-            .split(' ')
-            .filter_map(|v| v.parse::<f64>().ok())
-            .collect();
+    pub fn from_string(ualf_str: &str) -> Result<UalfData, UalfParseError> {
+        let columns: Vec<&str> = ualf_str.split_whitespace().collect();
+        if columns.len() != UALF_COLUMN_COUNT {
+            return Err(UalfParseError::ColumnCount(columns.len()));
+        }
 
-        let year = split_observation[1] as i32;
-        let month = split_observation[2] as u32;
-        let day = split_observation[3] as u32;
-        let hour = split_observation[4] as u32;
-        let minutes = split_observation[5] as u32;
-        let seconds = split_observation[6] as u32;
-        let nanos = split_observation[7] as u32;
+        let year = parse_column::<i32>(&columns, 1)?;
+        let month = parse_column::<u32>(&columns, 2)?;
+        let day = parse_column::<u32>(&columns, 3)?;
+        let hour = parse_column::<u32>(&columns, 4)?;
+        let minutes = parse_column::<u32>(&columns, 5)?;
+        let seconds = parse_column::<u32>(&columns, 6)?;
+        let nanos = parse_column::<u32>(&columns, 7)?;
 
         let epoch = NaiveDate::from_ymd_opt(year, month, day)
-            .unwrap()
-            .and_hms_nano_opt(hour, minutes, seconds, nanos)
-            .unwrap()
-            .and_utc()
-            .timestamp_nanos_opt()
-            .unwrap();
-
-        return Some(UalfData {
+            .and_then(|date| date.and_hms_nano_opt(hour, minutes, seconds, nanos))
+            .and_then(|datetime| datetime.and_utc().timestamp_nanos_opt())
+            .ok_or(UalfParseError::InvalidDate)?;
+
+        let latitude = parse_column::<f64>(&columns, 8)?;
+        let longitude = parse_column::<f64>(&columns, 9)?;
+        if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+            return Err(UalfParseError::CoordinatesOutOfRange {
+                latitude,
+                longitude,
+            });
+        }
+
+        Ok(UalfData {
+            version: parse_column(&columns, 0)?,
             epoch_ns: epoch,
-            latitude: split_observation[8],
-            longitude: split_observation[9],
-            peak_current: split_observation[10] as i16,
-            cloud_indicator: split_observation[21] != 0f64,
-        });
+            latitude,
+            longitude,
+            peak_current: parse_column(&columns, 10)?,
+            multiplicity: parse_column(&columns, 11)?,
+            sensors: parse_column(&columns, 12)?,
+            degrees_of_freedom: parse_column(&columns, 13)?,
+            ellipse_angle: parse_column(&columns, 14)?,
+            semi_major_axis_km: parse_column(&columns, 15)?,
+            semi_minor_axis_km: parse_column(&columns, 16)?,
+            chi_square: parse_column(&columns, 17)?,
+            rise_time_us: parse_column(&columns, 18)?,
+            peak_to_zero_time_us: parse_column(&columns, 19)?,
+            max_rate_of_rise: parse_column(&columns, 20)?,
+            cloud_indicator: parse_column::<u8>(&columns, 21)? != 0,
+            angle_indicator: parse_column::<u8>(&columns, 22)? != 0,
+            signal_indicator: parse_column::<u8>(&columns, 23)? != 0,
+            timing_indicator: parse_column::<u8>(&columns, 24)? != 0,
+        })
+    }
+
+    /// Formats the observation as a single UALF line (without newline). Numbers
+    /// are written in their shortest form that parses back to the same value,
+    /// so `from_string` reads back the same record.
+    pub fn to_ualf_line(&self) -> String {
+        let timestamp = self.timestamp();
+        format!(
+            "{} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {}",
+            self.version,
+            timestamp.year(),
+            timestamp.month(),
+            timestamp.day(),
+            timestamp.hour(),
+            timestamp.minute(),
+            timestamp.second(),
+            timestamp.nanosecond(),
+            self.latitude,
+            self.longitude,
+            self.peak_current,
+            self.multiplicity,
+            self.sensors,
+            self.degrees_of_freedom,
+            self.ellipse_angle,
+            self.semi_major_axis_km,
+            self.semi_minor_axis_km,
+            self.chi_square,
+            self.rise_time_us,
+            self.peak_to_zero_time_us,
+            self.max_rate_of_rise,
+            self.cloud_indicator as u8,
+            self.angle_indicator as u8,
+            self.signal_indicator as u8,
+            self.timing_indicator as u8,
+        )
+    }
+
+    /// A stroke at the given time, place and current, with plausible values
+    /// for the other columns. For synthetic data in tests and benchmarks.
+    pub fn synthetic(epoch_ns: i64, latitude: f64, longitude: f64, peak_current: i16) -> UalfData {
+        UalfData {
+            version: 0,
+            epoch_ns,
+            latitude,
+            longitude,
+            peak_current,
+            multiplicity: 0,
+            sensors: 6,
+            degrees_of_freedom: 4,
+            ellipse_angle: 0.0,
+            semi_major_axis_km: 0.5,
+            semi_minor_axis_km: 0.5,
+            chi_square: 1.0,
+            rise_time_us: 5.0,
+            peak_to_zero_time_us: 20.0,
+            max_rate_of_rise: 0.0,
+            cloud_indicator: false,
+            angle_indicator: true,
+            signal_indicator: false,
+            timing_indicator: true,
+        }
+    }
+
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp_nanos(self.epoch_ns)
+    }
+
+    pub fn is_stroke(&self) -> bool {
+        self.multiplicity == 0
+    }
+}
+
+/// Reads UALF lines one at a time, skipping blank lines, and returns the
+/// observations along with the number of lines that could not be parsed.
+pub fn parse_ualf<R: BufRead>(reader: R) -> io::Result<(Vec<UalfData>, usize)> {
+    let mut observations: Vec<UalfData> = vec![];
+    let mut rejected_lines = 0;
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match UalfData::from_string(&line) {
+            Ok(observation) => observations.push(observation),
+            Err(err) => {
+                debug!("Rejected UALF line '{}': {}", line, err);
+                rejected_lines += 1;
+            }
+        }
+    }
+
+    Ok((observations, rejected_lines))
+}
+
+/// Reads UALF lines like `parse_ualf`, logging the number of lines that could
+/// not be parsed.
+pub fn read_ualf<R: BufRead>(reader: R) -> io::Result<Vec<UalfData>> {
+    let (observations, rejected_lines) = parse_ualf(reader)?;
+    if rejected_lines > 0 {
+        warn!(
+            "Rejected {} malformed UALF lines out of {}",
+            rejected_lines,
+            rejected_lines + observations.len()
+        );
+    }
+
+    Ok(observations)
+}
+
+/// Writes observations as newline-terminated UALF lines.
+pub fn write_ualf<W: Write>(writer: &mut W, observations: &[UalfData]) -> io::Result<()> {
+    for observation in observations {
+        writeln!(writer, "{}", observation.to_ualf_line())?;
+    }
+    Ok(())
+}
+
+/// Where the unit tests put their storms, as (latitude, longitude).
+#[cfg(test)]
+pub(crate) const TEST_ORIGIN: (f64, f64) = (60.0, 10.0);
+
+/// A synthetic stroke `north_km` and `east_km` from `TEST_ORIGIN`.
+#[cfg(test)]
+pub(crate) fn strike_near(epoch_ns: i64, north_km: f64, east_km: f64) -> UalfData {
+    let (latitude, longitude) =
+        crate::projection::AzimuthalEquidistant::new(TEST_ORIGIN).unproject((north_km, east_km));
+    UalfData::synthetic(epoch_ns, latitude, longitude, -12)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINE: &str = concat!(
+        "0 2024 7 1 12 30 15 123456789 59.9123 10.7456 -12 1 6 4 ",
+        "45.5 0.6 0.3 1.2 5.3 12.1 3.2 0 1 0 1"
+    );
+
+    fn with_column(column: usize, value: &str) -> String {
+        replace_column(LINE, column, value)
+    }
+
+    fn replace_column(line: &str, column: usize, value: &str) -> String {
+        let mut columns: Vec<&str> = line.split_whitespace().collect();
+        columns[column] = value;
+        columns.join(" ")
+    }
+
+    #[test]
+    fn parses_every_column() {
+        let observation = UalfData::from_string(LINE).unwrap();
+        assert_eq!(
+            observation.timestamp().to_rfc3339(),
+            "2024-07-01T12:30:15.123456789+00:00"
+        );
+        assert_eq!(
+            (observation.latitude, observation.longitude),
+            (59.9123, 10.7456)
+        );
+        assert_eq!(observation.peak_current, -12);
+        assert_eq!(observation.chi_square, 1.2);
+        assert!(!observation.cloud_indicator);
+        assert!(observation.timing_indicator);
+    }
+
+    #[test]
+    fn round_trips_through_ualf_line() {
+        let observation = UalfData::from_string(LINE).unwrap();
+        assert_eq!(observation.to_ualf_line(), LINE);
+
+        let line = replace_column(&with_column(17, "1.234"), 18, "5.35");
+        let precise = UalfData::from_string(&line).unwrap();
+        assert_eq!(precise.chi_square, 1.234);
+        assert_eq!(precise.rise_time_us, 5.35);
+        assert_eq!(
+            UalfData::from_string(&precise.to_ualf_line()).unwrap(),
+            precise
+        );
+    }
+
+    #[test]
+    fn write_then_read_gives_the_same_observations() {
+        let observations: Vec<UalfData> = [LINE, &with_column(8, "-33.8688123456")]
+            .iter()
+            .map(|line| UalfData::from_string(line).unwrap())
+            .collect();
+        let mut written: Vec<u8> = vec![];
+        write_ualf(&mut written, &observations).unwrap();
+        assert_eq!(read_ualf(written.as_slice()).unwrap(), observations);
+    }
+
+    #[test]
+    fn rejects_wrong_column_count() {
+        let truncated = &LINE[..LINE.rfind(' ').unwrap()];
+        assert_eq!(
+            UalfData::from_string(truncated).unwrap_err(),
+            UalfParseError::ColumnCount(24)
+        );
+        assert_eq!(
+            UalfData::from_string("").unwrap_err(),
+            UalfParseError::ColumnCount(0)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_number() {
+        assert_eq!(
+            UalfData::from_string(&with_column(10, "-1x")).unwrap_err(),
+            UalfParseError::InvalidNumber {
+                column: 10,
+                value: "-1x".to_string()
+            }
+        );
+        // Out of range for the column type
+        assert_eq!(
+            UalfData::from_string(&with_column(11, "300")).unwrap_err(),
+            UalfParseError::InvalidNumber {
+                column: 11,
+                value: "300".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_date() {
+        assert_eq!(
+            UalfData::from_string(&with_column(2, "13")).unwrap_err(),
+            UalfParseError::InvalidDate
+        );
+        assert_eq!(
+            UalfData::from_string(&replace_column(&with_column(2, "6"), 3, "31")).unwrap_err(),
+            UalfParseError::InvalidDate
+        );
+        assert_eq!(
+            UalfData::from_string(&with_column(4, "24")).unwrap_err(),
+            UalfParseError::InvalidDate
+        );
+    }
+
+    #[test]
+    fn rejects_coordinates_out_of_range() {
+        assert_eq!(
+            UalfData::from_string(&with_column(8, "91.0")).unwrap_err(),
+            UalfParseError::CoordinatesOutOfRange {
+                latitude: 91.0,
+                longitude: 10.7456
+            }
+        );
+        assert_eq!(
+            UalfData::from_string(&with_column(9, "-180.5")).unwrap_err(),
+            UalfParseError::CoordinatesOutOfRange {
+                latitude: 59.9123,
+                longitude: -180.5
+            }
+        );
+    }
+
+    #[test]
+    fn counts_rejected_lines() {
+        let text = format!(
+            "{}\n\n{}\nnot ualf at all\n{}\n",
+            LINE,
+            with_column(8, "95.0"),
+            with_column(1, "2025")
+        );
+        let (observations, rejected_lines) = parse_ualf(text.as_bytes()).unwrap();
+        assert_eq!(observations.len(), 2);
+        assert_eq!(rejected_lines, 2);
+        assert_eq!(read_ualf(text.as_bytes()).unwrap().len(), 2);
     }
 }
@@ -1,37 +1,210 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io;
+use std::mem;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
 use crate::ualf::UalfData;
 
-const PROCESSED_BUFFER_SIZE: usize = 8192;
+const DEFAULT_WINDOW: Duration = Duration::from_secs(10 * 60);
+
+/// Identifies a stroke. Two strokes at the same instant are only the same
+/// observation if they also share position and peak current.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ObservationKey {
+    pub epoch_ns: i64,
+    pub latitude_bits: u64,
+    pub longitude_bits: u64,
+    pub peak_current: i16,
+}
 
+impl ObservationKey {
+    pub fn new(observation: &UalfData) -> ObservationKey {
+        ObservationKey {
+            epoch_ns: observation.epoch_ns,
+            latitude_bits: observation.latitude.to_bits(),
+            longitude_bits: observation.longitude.to_bits(),
+            peak_current: observation.peak_current,
+        }
+    }
+}
+
+/// The on-disk form of a `UalfBuffer`.
+#[derive(Serialize, Deserialize)]
+struct UalfBufferState {
+    newest_epoch_ns: i64,
+    keys: Vec<ObservationKey>,
+}
+
+/// Remembers which observations have been processed within the polled window.
+///
+/// A poll of the latest `window` can never return strokes older than the
+/// newest stroke seen minus `window`, so those are evicted after every poll.
 pub struct UalfBuffer {
-    pub processed_observations: [i64; PROCESSED_BUFFER_SIZE],
-    pub processed_observation_index: usize,
+    seen: HashSet<ObservationKey>,
+    by_time: BTreeMap<i64, Vec<ObservationKey>>,
+    window_ns: i64,
+    newest_epoch_ns: i64,
+}
+
+impl Default for UalfBuffer {
+    fn default() -> Self {
+        UalfBuffer::new(DEFAULT_WINDOW)
+    }
 }
 
 impl UalfBuffer {
-    pub fn new() -> UalfBuffer {
-        return UalfBuffer {
-            processed_observations: [0; PROCESSED_BUFFER_SIZE],
-            processed_observation_index: 0,
+    pub fn new(window: Duration) -> UalfBuffer {
+        UalfBuffer {
+            seen: HashSet::new(),
+            by_time: BTreeMap::new(),
+            window_ns: window.as_nanos() as i64,
+            newest_epoch_ns: i64::MIN,
+        }
+    }
+
+    /// Restores a buffer saved with `save`, evicting anything outside `window`.
+    pub fn load(path: &Path, window: Duration) -> io::Result<UalfBuffer> {
+        let state: UalfBufferState = serde_json::from_slice(&fs::read(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let mut buffer = UalfBuffer::new(window);
+        buffer.newest_epoch_ns = state.newest_epoch_ns;
+        for key in state.keys {
+            if buffer.seen.insert(key) {
+                buffer.by_time.entry(key.epoch_ns).or_default().push(key);
+            }
+        }
+        buffer.evict();
+        Ok(buffer)
+    }
+
+    /// Writes the buffer to `path`, replacing the previous checkpoint atomically.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let state = UalfBufferState {
+            newest_epoch_ns: self.newest_epoch_ns,
+            keys: self.seen.iter().copied().collect(),
         };
+        let json = serde_json::to_vec(&state)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, json)?;
+        fs::rename(&tmp_path, path)
     }
 
-    pub fn get_unchecked_observations(&mut self, observations: &Vec<UalfData>) -> Vec<UalfData> {
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+
+    pub fn get_unchecked_observations(&mut self, observations: &[UalfData]) -> Vec<UalfData> {
         let mut unchecked_observations: Vec<UalfData> = vec![];
 
         for obs in observations {
-            if !self.processed_observations.contains(&obs.epoch_ns) {
-                unchecked_observations.push(obs.clone())
+            let key = ObservationKey::new(obs);
+            if self.seen.insert(key) {
+                self.by_time.entry(obs.epoch_ns).or_default().push(key);
+                self.newest_epoch_ns = self.newest_epoch_ns.max(obs.epoch_ns);
+                unchecked_observations.push(obs.clone());
             }
         }
-        if self.processed_observation_index >= PROCESSED_BUFFER_SIZE {
-            self.processed_observation_index = 0
-        }
-        for obs in &unchecked_observations {
-            self.processed_observations[self.processed_observation_index % PROCESSED_BUFFER_SIZE] =
-                obs.epoch_ns;
-            self.processed_observation_index += 1;
+        self.evict();
+
+        unchecked_observations
+    }
+
+    fn evict(&mut self) {
+        let horizon = self.newest_epoch_ns.saturating_sub(self.window_ns);
+        let kept = self.by_time.split_off(&horizon);
+        let expired = mem::replace(&mut self.by_time, kept);
+        for key in expired.into_values().flatten() {
+            self.seen.remove(&key);
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE_NS: i64 = 60 * 1_000_000_000;
+
+    #[test]
+    fn strokes_at_the_same_instant_are_told_apart() {
+        let mut buffer = UalfBuffer::default();
+        let strokes = [
+            UalfData::synthetic(0, 60.0, 10.0, -12),
+            UalfData::synthetic(0, 60.5, 10.0, -12),
+            UalfData::synthetic(0, 60.0, 10.5, -12),
+            UalfData::synthetic(0, 60.0, 10.0, 25),
+        ];
+        assert_eq!(buffer.get_unchecked_observations(&strokes).len(), 4);
+        assert_eq!(buffer.len(), 4);
+    }
+
+    #[test]
+    fn repeated_strokes_are_only_returned_once() {
+        let mut buffer = UalfBuffer::default();
+        let first = UalfData::synthetic(0, 60.0, 10.0, -12);
+        let second = UalfData::synthetic(MINUTE_NS, 60.1, 10.0, -8);
+        assert_eq!(
+            buffer.get_unchecked_observations(&[first.clone(), first.clone()]),
+            vec![first.clone()]
+        );
+        assert_eq!(
+            buffer.get_unchecked_observations(&[first, second.clone()]),
+            vec![second]
+        );
+    }
+
+    #[test]
+    fn reloads_what_was_saved() {
+        let path = std::env::temp_dir().join(format!("ualf_buffer_{}.json", std::process::id()));
+        let strokes = [
+            UalfData::synthetic(0, 60.0, 10.0, -12),
+            UalfData::synthetic(0, 60.5, 10.0, -12),
+            UalfData::synthetic(5 * MINUTE_NS, 60.1, 10.0, 8),
+        ];
+        let mut buffer = UalfBuffer::default();
+        buffer.get_unchecked_observations(&strokes);
+        buffer.save(&path).unwrap();
+
+        let mut reloaded = UalfBuffer::load(&path, DEFAULT_WINDOW).unwrap();
+        assert_eq!(reloaded.len(), 3);
+        assert!(reloaded.get_unchecked_observations(&strokes).is_empty());
+
+        // A narrower window than the one saved with evicts on load
+        let narrower = UalfBuffer::load(&path, Duration::from_secs(60));
+        fs::remove_file(&path).unwrap();
+        assert_eq!(narrower.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn evicts_strokes_that_fall_out_of_the_window() {
+        let mut buffer = UalfBuffer::new(Duration::from_secs(10 * 60));
+        let oldest = [UalfData::synthetic(0, 60.0, 10.0, -12)];
+        buffer.get_unchecked_observations(&oldest);
+
+        // Exactly one window newer, the oldest stroke can still be polled again
+        buffer.get_unchecked_observations(&[UalfData::synthetic(10 * MINUTE_NS, 60.1, 10.0, -12)]);
+        assert_eq!(buffer.len(), 2);
+        assert!(buffer.get_unchecked_observations(&oldest).is_empty());
 
-        return unchecked_observations;
+        // One nanosecond more and it is forgotten
+        buffer.get_unchecked_observations(&[UalfData::synthetic(
+            10 * MINUTE_NS + 1,
+            60.2,
+            10.0,
+            -12,
+        )]);
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(buffer.get_unchecked_observations(&oldest).len(), 1);
     }
 }
@@ -0,0 +1,188 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::info;
+
+use crate::lightning_source::{LightningSource, SourceError, SourceHealth};
+use crate::ualf::{read_ualf, write_ualf, UalfData};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Opens a UALF archive for reading. A path of `-` reads from stdin, and gzip
+/// compressed input is detected from its magic bytes.
+pub fn open_ualf_reader(path: &str) -> io::Result<Box<dyn BufRead>> {
+    let mut reader: Box<dyn BufRead> = if path == "-" {
+        Box::new(BufReader::new(io::stdin()))
+    } else {
+        Box::new(BufReader::new(File::open(path)?))
+    };
+
+    if reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        reader = Box::new(BufReader::new(MultiGzDecoder::new(reader)));
+    }
+
+    Ok(reader)
+}
+
+pub fn read_ualf_file(path: &str) -> io::Result<Vec<UalfData>> {
+    let observations = read_ualf(open_ualf_reader(path)?)?;
+    info!("Read {} observations from {}", observations.len(), path);
+    Ok(observations)
+}
+
+/// Reads every archive in `paths` and returns the observations sorted by time.
+pub fn read_ualf_files(paths: &[String]) -> io::Result<Vec<UalfData>> {
+    let mut observations: Vec<UalfData> = vec![];
+    for path in paths {
+        observations.extend(read_ualf_file(path)?);
+    }
+    observations.sort_by_key(|obs| obs.epoch_ns);
+    Ok(observations)
+}
+
+/// Appends observations to a UALF archive, creating it if needed. A path ending
+/// in `.gz` gets a gzip member per call, which `open_ualf_reader` reads back as
+/// one stream.
+pub fn append_ualf_file(path: &Path, observations: &[UalfData]) -> io::Result<()> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    if path.extension().is_some_and(|extension| extension == "gz") {
+        let mut writer = GzEncoder::new(BufWriter::new(file), Compression::default());
+        write_ualf(&mut writer, observations)?;
+        writer.finish()?.flush()
+    } else {
+        let mut writer = BufWriter::new(file);
+        write_ualf(&mut writer, observations)?;
+        writer.flush()
+    }
+}
+
+/// Replays UALF archives as a `LightningSource`. The replay clock starts at the
+/// first observation and advances `speed` times faster than the wall clock.
+pub struct ReplaySource {
+    observations: Vec<UalfData>,
+    start_epoch_ns: i64,
+    started: Instant,
+    speed: f64,
+}
+
+impl ReplaySource {
+    pub fn new(observations: Vec<UalfData>, speed: f64) -> ReplaySource {
+        let mut observations = observations;
+        observations.sort_by_key(|obs| obs.epoch_ns);
+        ReplaySource {
+            start_epoch_ns: observations.first().map_or(0, |obs| obs.epoch_ns),
+            observations,
+            started: Instant::now(),
+            speed,
+        }
+    }
+
+    pub fn from_files(paths: &[String], speed: f64) -> io::Result<ReplaySource> {
+        Ok(ReplaySource::new(read_ualf_files(paths)?, speed))
+    }
+}
+
+impl LightningSource for ReplaySource {
+    fn name(&self) -> &str {
+        "replay"
+    }
+
+    async fn fetch(&self, window: Duration) -> Result<Vec<UalfData>, SourceError> {
+        let now_ns = self.now_epoch_ns();
+        let oldest_ns = now_ns - window.as_nanos() as i64;
+        let start = self
+            .observations
+            .partition_point(|obs| obs.epoch_ns <= oldest_ns);
+        let end = self
+            .observations
+            .partition_point(|obs| obs.epoch_ns <= now_ns);
+        Ok(self.observations[start..end].to_vec())
+    }
+
+    fn health(&self) -> SourceHealth {
+        if self.is_finished() {
+            return SourceHealth::Down("replay finished".to_string());
+        }
+        SourceHealth::Healthy
+    }
+
+    fn now_epoch_ns(&self) -> i64 {
+        let elapsed_ns = self.started.elapsed().as_nanos() as f64 * self.speed;
+        self.start_epoch_ns + elapsed_ns as i64
+    }
+
+    fn is_finished(&self) -> bool {
+        self.observations
+            .last()
+            .is_none_or(|obs| obs.epoch_ns < self.now_epoch_ns())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+
+    const LINES: [&str; 2] = [
+        concat!(
+            "0 2024 7 1 12 30 15 123456789 59.9123 10.7456 -12 1 6 4 ",
+            "45.5 0.6 0.3 1.234 5.35 12.1 3.2 0 1 0 1"
+        ),
+        "1 2024 7 1 12 30 16 5 60.01 10.9 31 0 12 9 0 0.25 0.1 0.8 2 20.5 7.125 1 0 1 0",
+    ];
+
+    fn archive_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("ualf_file_{}_{}", process::id(), name))
+    }
+
+    fn append_and_read_back(name: &str) {
+        let observations: Vec<UalfData> = LINES
+            .iter()
+            .map(|line| UalfData::from_string(line).unwrap())
+            .collect();
+        let path = archive_path(name);
+        let _ = fs::remove_file(&path);
+
+        append_ualf_file(&path, &observations[..1]).unwrap();
+        append_ualf_file(&path, &observations[1..]).unwrap();
+        let read = read_ualf_file(path.to_str().unwrap());
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(read.unwrap(), observations);
+        assert_eq!(bytes.starts_with(&GZIP_MAGIC), name.ends_with(".gz"));
+    }
+
+    #[test]
+    fn replay_clock_starts_at_the_first_observation() {
+        let observations: Vec<UalfData> = LINES
+            .iter()
+            .map(|line| UalfData::from_string(line).unwrap())
+            .collect();
+        let first_epoch_ns = observations[0].epoch_ns;
+        let replay = ReplaySource::new(observations, 1000.0);
+
+        let now_ns = replay.now_epoch_ns();
+        assert!(now_ns >= first_epoch_ns && now_ns < first_epoch_ns + 60_000_000_000);
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(replay.now_epoch_ns() >= now_ns + 5_000_000_000);
+    }
+
+    #[test]
+    fn plain_archive_round_trips() {
+        append_and_read_back("plain.ualf");
+    }
+
+    #[test]
+    fn gzip_archive_round_trips() {
+        append_and_read_back("compressed.ualf.gz");
+    }
+}